@@ -0,0 +1,88 @@
+// Build Script
+// ============
+// Cargo runs this on the host before compiling the kernel. It records the
+// git commit and the build time in environment variables that the
+// `build_info` module reads with `env!`.
+//
+// Study Notes:
+// - Lines printed as `cargo:rustc-env=NAME=value` become compile-time
+//   environment variables of the crate
+// - Without git (e.g. building from a source archive) we fall back to
+//   "unknown" instead of failing the build
+// - Cargo only reruns the script for the paths it prints with
+//   `rerun-if-changed` (a directory counts every file in it), so
+//   BLOG_OS_BUILD_TIME is the time of the last build that changed one of
+//   them, not of every `cargo build`
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    let git_hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".into());
+    let dirty = git(&["status", "--porcelain"]).is_some_and(|status| !status.is_empty());
+    let suffix = if dirty { "-dirty" } else { "" };
+    println!("cargo:rustc-env=BLOG_OS_GIT_HASH={}{}", git_hash, suffix);
+    println!("cargo:rustc-env=BLOG_OS_BUILD_TIME={}", build_time());
+
+    // Rerun when a commit is made or checked out, when files are staged
+    // (which can change the dirty flag), and when a source file is edited,
+    // so the hash, the `-dirty` suffix and the build time stay current
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/logs/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/index", git_dir);
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// Runs git and returns its trimmed output, or `None` if that fails
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().into())
+}
+
+/// Returns the build time as `YYYY-MM-DD HH:MM:SS UTC`
+///
+/// SOURCE_DATE_EPOCH overrides the clock for reproducible builds.
+fn build_time() -> String {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .or_else(|| Some(SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs()));
+    let Some(seconds) = seconds else {
+        return "unknown".into();
+    };
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm, which avoids
+/// pulling in a date crate just for the build time.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,123 @@
+// Boot Banner
+// ===========
+// This module builds the boot information screen from a list of sections
+// and key/value pairs, so the layout is defined in one place instead of
+// being spread over many println! calls.
+//
+// Study Notes:
+// - The banner is built with chained method calls (the builder pattern)
+// - Lines are stored in a fixed-size array, so no heap is needed
+// - Values are anything that implements Display; `format_args!` works as
+//   long as the banner is built and rendered in a single statement
+
+use crate::vga_buffer::{Color, Writer};
+use core::fmt;
+
+/// The maximum number of sections and key/value pairs in a banner
+const MAX_LINES: usize = 16;
+
+/// A line of the banner below the title
+#[derive(Clone, Copy)]
+enum Line<'a> {
+    Section(&'a str),
+    Kv(&'a str, &'a dyn fmt::Display),
+}
+
+/// A boot screen made of a centered title, underlined sections and aligned
+/// key/value pairs
+pub struct BootBanner<'a> {
+    title: Option<&'a str>,
+    lines: [Option<Line<'a>>; MAX_LINES],
+    len: usize,
+}
+
+impl<'a> BootBanner<'a> {
+    /// Creates an empty banner
+    pub fn new() -> Self {
+        BootBanner {
+            title: None,
+            lines: [None; MAX_LINES],
+            len: 0,
+        }
+    }
+
+    /// Sets the title, which is shown centered above everything else
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Starts a new section with an underlined heading
+    pub fn section(self, name: &'a str) -> Self {
+        self.push(Line::Section(name))
+    }
+
+    /// Adds a key/value pair to the current section
+    pub fn kv(self, key: &'a str, value: &'a dyn fmt::Display) -> Self {
+        self.push(Line::Kv(key, value))
+    }
+
+    /// Appends a line, panicking if the banner is full
+    fn push(mut self, line: Line<'a>) -> Self {
+        assert!(self.len < MAX_LINES, "boot banner has more than {} lines", MAX_LINES);
+        self.lines[self.len] = Some(line);
+        self.len += 1;
+        self
+    }
+
+    /// Writes the banner, starting on a new line
+    pub fn render(&self, writer: &mut Writer) {
+        writer.write_byte(b'\n');
+        if let Some(title) = self.title {
+            let padding = writer.width().saturating_sub(title.len()) / 2;
+            for _ in 0..padding {
+                writer.write_byte(b' ');
+            }
+            writer.with_color(Color::White, Color::Black, |w| w.write_string(title));
+            writer.write_byte(b'\n');
+        }
+        for line in self.lines[..self.len].iter().flatten() {
+            match *line {
+                Line::Section(name) => {
+                    writer.with_color(Color::LightGreen, Color::Black, |w| {
+                        w.write_byte(b'\n');
+                        w.write_string(name);
+                        w.write_byte(b'\n');
+                        for _ in 0..name.len() {
+                            w.write_byte(b'-');
+                        }
+                        w.write_byte(b'\n');
+                    });
+                }
+                Line::Kv(key, value) => writer.write_kv(key, format_args!("{}", value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vga_buffer::WRITER;
+
+    #[test_case]
+    fn test_banner_aligns_fields() {
+        let mut writer = WRITER.lock();
+        BootBanner::new()
+            .title("Title")
+            .section("Info")
+            .kv("Short", &1)
+            .kv("Longer key", &format_args!("0x{:X}", 0xb8000))
+            .render(&mut writer);
+
+        let (short, long) = (writer.height() - 3, writer.height() - 2);
+        let separator = (0..writer.width())
+            .find(|&col| writer.read_char_at(short, col).character() == b':')
+            .unwrap();
+        assert_eq!(writer.read_char_at(long, separator).character(), b':');
+        assert_eq!(writer.read_char_at(short, separator + 2).character(), b'1');
+        assert_eq!(writer.read_char_at(long, separator + 2).character(), b'0');
+        assert_eq!(writer.read_char_at(short - 1, 0).character(), b'-');
+        assert_eq!(writer.read_char_at(short - 2, 0).character(), b'I');
+    }
+}
@@ -0,0 +1,33 @@
+// Build Information
+// =================
+// This module tells which source a running kernel was built from. The
+// version comes from Cargo.toml; the git commit and build time are recorded
+// by `build.rs`.
+//
+// Study Notes:
+// - `env!` reads an environment variable at compile time, so these strings
+//   are baked into the kernel image
+// - A `-dirty` suffix on the git hash means the tree had uncommitted changes
+
+/// The crate version from Cargo.toml
+#[allow(dead_code)]
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The short git commit hash, or "unknown" when built without git
+pub const GIT_HASH: &str = env!("BLOG_OS_GIT_HASH");
+
+/// The UTC build time, or "unknown" if the clock couldn't be read
+pub const BUILD_TIME: &str = env!("BLOG_OS_BUILD_TIME");
+
+/// Returns a one-line description like `0.1.0 (1a2b3c4, 2024-01-01 12:00:00 UTC)`
+#[allow(dead_code)]
+pub fn version() -> &'static str {
+    concat!(
+        env!("CARGO_PKG_VERSION"),
+        " (",
+        env!("BLOG_OS_GIT_HASH"),
+        ", ",
+        env!("BLOG_OS_BUILD_TIME"),
+        ")"
+    )
+}
@@ -0,0 +1,104 @@
+// Code Page 437
+// =============
+// This module maps Unicode characters to the glyphs of code page 437, the
+// character set built into the VGA font. ASCII maps to itself; the other
+// 160 glyphs (accented letters, box drawing, Greek letters, ...) have codes
+// below 0x20 and above 0x7e.
+//
+// Study Notes:
+// - In text, bytes 0x01..0x1f and 0x7f are control characters, but the VGA
+//   font still has a glyph for each of them (like ☺ for 0x01)
+// - Rust strings are UTF-8, so a char like 'é' is two bytes in a &str, but
+//   one byte (0x82) in the VGA buffer
+
+/// The glyphs of codes 0x01..=0x1f
+const LOW_GLYPHS: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// The glyph of code 0x7f
+const DELETE_GLYPH: char = '⌂';
+
+/// The glyphs of codes 0x80..=0xff
+const HIGH_GLYPHS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Returns the code page 437 code of a character's glyph, if it has one
+///
+/// Printable ASCII maps to itself. ASCII control characters return `None`:
+/// they are handled by the writer, not drawn.
+pub fn from_char(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        DELETE_GLYPH => Some(0x7f),
+        _ => {
+            // STUDY NOTE: A linear search over 159 entries is fast enough for
+            // text output and needs no extra table in memory.
+            let low = LOW_GLYPHS.iter().position(|&g| g == c).map(|i| i as u8 + 0x01);
+            low.or_else(|| HIGH_GLYPHS.iter().position(|&g| g == c).map(|i| i as u8 + 0x80))
+        }
+    }
+}
+
+/// Returns the character drawn for a code page 437 code
+///
+/// Code 0 (an empty cell) is shown as a space.
+#[allow(dead_code)]
+pub fn to_char(code: u8) -> char {
+    match code {
+        0x00 => ' ',
+        0x01..=0x1f => LOW_GLYPHS[usize::from(code - 0x01)],
+        0x20..=0x7e => char::from(code),
+        0x7f => DELETE_GLYPH,
+        0x80..=0xff => HIGH_GLYPHS[usize::from(code - 0x80)],
+    }
+}
+
+/// Returns whether a character is a combining mark, like U+0301 (´)
+///
+/// STUDY NOTE: A combining mark modifies the character before it, e.g.
+/// "e\u{301}" renders as é. Text mode draws exactly one glyph per cell and
+/// can't lay two glyphs on top of each other, so the writer drops these.
+pub fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_round_trip() {
+        // Every code except the empty cell maps to a glyph and back, which
+        // also catches a character entered twice in the tables
+        for code in 0x01..=0xff {
+            assert_eq!(from_char(to_char(code)), Some(code));
+        }
+        assert_eq!(to_char(0x00), ' ');
+    }
+
+    #[test_case]
+    fn test_known_glyphs() {
+        assert_eq!(from_char('é'), Some(0x82));
+        assert_eq!(from_char('╔'), Some(0xc9));
+        assert_eq!(from_char('☺'), Some(0x01));
+        assert_eq!(from_char('\n'), None);
+        assert_eq!(from_char('€'), None);
+    }
+}
@@ -0,0 +1,108 @@
+// C-Style Printing
+// ================
+// This module provides `cprintf`, a tiny printf for code that calls into the
+// kernel through the C ABI (assembly stubs or ported C code). Rust code
+// should use `print!` and `println!` instead.
+//
+// Study Notes:
+// - A C variadic function gets its extra arguments without any type
+//   information; the format string is the only thing that says how many
+//   there are and what types they have
+// - Rust supports defining such functions with the nightly `c_variadic`
+//   feature: the `...` parameter is a `VaList` we take arguments from
+// - Reading an argument with the wrong type is undefined behavior, which is
+//   why every call of `cprintf` is unsafe
+
+use crate::vga_buffer::{WRITER, output_enabled};
+use core::ffi::{CStr, VaList, c_char, c_int, c_uint};
+use core::fmt::Write;
+
+/// Prints a printf-style format string to the VGA text buffer
+///
+/// Supports `%d`, `%x`, `%s`, `%c` and `%%`. Other conversions are printed
+/// as they are. A null format prints nothing, a null `%s` prints `(null)`.
+/// Nothing is printed while output is disabled (see `set_output_enabled`).
+///
+/// # Safety
+///
+/// `format` must be null or point to a NUL-terminated string, and the
+/// arguments must match its conversions: `c_int` for `%d` and `%c`,
+/// `c_uint` for `%x` and a null or NUL-terminated `*const c_char` for `%s`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cprintf(format: *const c_char, args: ...) {
+    unsafe { vcprintf(format, args) }
+}
+
+/// Like `cprintf`, but takes the arguments as a `VaList`
+///
+/// # Safety
+///
+/// See `cprintf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vcprintf(format: *const c_char, mut args: VaList) {
+    if format.is_null() || !output_enabled() {
+        return;
+    }
+    let format = unsafe { CStr::from_ptr(format) }.to_bytes();
+    let mut writer = WRITER.lock();
+    let mut bytes = format.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            writer.write_bytes(&[byte]);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'd') => write!(writer, "{}", unsafe { args.next_arg::<c_int>() }).unwrap(),
+            Some(b'x') => write!(writer, "{:x}", unsafe { args.next_arg::<c_uint>() }).unwrap(),
+            Some(b'c') => writer.write_bytes(&[unsafe { args.next_arg::<c_int>() } as u8]),
+            Some(b's') => {
+                let s = unsafe { args.next_arg::<*const c_char>() };
+                if s.is_null() {
+                    writer.write_string("(null)");
+                } else {
+                    writer.write_bytes(unsafe { CStr::from_ptr(s) }.to_bytes());
+                }
+            }
+            Some(b'%') => writer.write_byte(b'%'),
+            Some(&other) => writer.write_bytes(&[b'%', other]),
+            None => writer.write_byte(b'%'),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_cprintf_conversions() {
+        let expected = b"-42 ff hi z % (null)";
+        unsafe {
+            cprintf(
+                c"\n%d %x %s %c %% %s".as_ptr(),
+                -42 as c_int,
+                255 as c_uint,
+                c"hi".as_ptr(),
+                b'z' as c_int,
+                core::ptr::null::<c_char>(),
+            );
+            cprintf(core::ptr::null());
+        }
+        let writer = WRITER.lock();
+        let row = writer.height() - 1;
+        for (col, &c) in expected.iter().enumerate() {
+            assert_eq!(writer.read_char_at(row, col).character(), c);
+        }
+    }
+
+    #[test_case]
+    fn test_cprintf_silenced() {
+        use crate::vga_buffer::set_output_enabled;
+        unsafe { cprintf(c"\n".as_ptr()) };
+        set_output_enabled(false);
+        unsafe { cprintf(c"hidden".as_ptr()) };
+        set_output_enabled(true);
+        let writer = WRITER.lock();
+        assert_eq!(writer.read_char_at(writer.height() - 1, 0).character(), b' ');
+    }
+}
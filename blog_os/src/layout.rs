@@ -0,0 +1,145 @@
+// Screen Layouts
+// ==============
+// This module lets a static screen layout (like a HUD with a log pane and a
+// status pane) be declared once as a constant and then drawn and written to
+// by region name.
+//
+// Study Notes:
+// - `Region::new` and `Layout::new` are `const fn`, so a layout can be a
+//   `const` item and invalid regions are rejected at compile time
+// - A region's border is drawn on its outer cells; text goes into the
+//   interior, which is one cell smaller on every side
+// - Text written into a region is cut off at the region's right edge, so a
+//   long line can never spill into the neighbouring pane
+
+use crate::vga_buffer::{Color, Writer};
+
+/// A named rectangle on screen with a border color
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub name: &'static str,
+    pub row: usize,
+    pub col: usize,
+    pub width: usize,
+    pub height: usize,
+    pub color: Color,
+}
+
+impl Region {
+    /// Creates a region, including its border, at the given position
+    ///
+    /// Panics (at compile time when used in a `const`) if the region is
+    /// too small to have a border.
+    #[allow(dead_code)]
+    pub const fn new(
+        name: &'static str,
+        row: usize,
+        col: usize,
+        width: usize,
+        height: usize,
+        color: Color,
+    ) -> Region {
+        assert!(width >= 2 && height >= 2, "a region needs room for its border");
+        Region {
+            name,
+            row,
+            col,
+            width,
+            height,
+            color,
+        }
+    }
+
+    /// Draws the border in the region's color and clears the interior
+    #[allow(dead_code)]
+    pub fn render(&self, writer: &mut Writer) {
+        writer.with_color(self.color, Color::Black, |w| {
+            w.fill_region(self.row, self.col, self.width, self.height, Color::Black);
+            w.draw_box(self.row, self.col, self.width, self.height);
+        });
+    }
+
+    /// Writes text to a line of the region's interior
+    ///
+    /// Text wider than the interior is cut off and lines past the bottom
+    /// of the interior are ignored.
+    #[allow(dead_code)]
+    pub fn write_line(&self, writer: &mut Writer, line: usize, text: &str) {
+        let inner_width = self.width - 2;
+        if line >= self.height - 2 {
+            return;
+        }
+        let mut end = text.len().min(inner_width);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        writer.with_color(self.color, Color::Black, |w| {
+            w.write_string_at(self.row + 1 + line, self.col + 1, &text[..end]);
+        });
+    }
+}
+
+/// A fixed set of regions that make up a screen
+#[derive(Debug, Clone, Copy)]
+pub struct Layout<const N: usize> {
+    regions: [Region; N],
+}
+
+impl<const N: usize> Layout<N> {
+    /// Creates a layout from its regions
+    #[allow(dead_code)]
+    pub const fn new(regions: [Region; N]) -> Self {
+        Layout { regions }
+    }
+
+    /// Draws all regions
+    #[allow(dead_code)]
+    pub fn render(&self, writer: &mut Writer) {
+        for region in &self.regions {
+            region.render(writer);
+        }
+    }
+
+    /// Looks up a region by name
+    #[allow(dead_code)]
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /// Writes text to a line of the named region
+    ///
+    /// Panics if the layout has no region with that name.
+    #[allow(dead_code)]
+    pub fn write(&self, writer: &mut Writer, name: &str, line: usize, text: &str) {
+        match self.region(name) {
+            Some(region) => region.write_line(writer, line, text),
+            None => panic!("layout has no region named {:?}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vga_buffer::{BOX_BOTTOM_RIGHT, BOX_TOP_LEFT, BOX_VERTICAL, WRITER};
+
+    const TWO_PANES: Layout<2> = Layout::new([
+        Region::new("log", 0, 0, 60, 25, Color::LightGray),
+        Region::new("status", 0, 60, 20, 25, Color::LightCyan),
+    ]);
+
+    #[test_case]
+    fn test_layout_render_and_write() {
+        let mut writer = WRITER.lock();
+        TWO_PANES.render(&mut writer);
+        TWO_PANES.write(&mut writer, "status", 0, "a status line that is too long");
+
+        assert_eq!(writer.read_char_at(0, 60).character(), BOX_TOP_LEFT);
+        assert_eq!(writer.read_char_at(24, 79).character(), BOX_BOTTOM_RIGHT);
+        assert_eq!(writer.read_char_at(1, 61).character(), b'a');
+        assert_eq!(writer.read_char_at(1, 78).character(), b't');
+        // The text is cut off before the right border
+        assert_eq!(writer.read_char_at(1, 79).character(), BOX_VERTICAL);
+        assert_eq!(writer.read_char_at(1, 61).foreground(), Color::LightCyan);
+    }
+}
@@ -5,20 +5,55 @@
 
 #![no_std]  // Don't link the Rust standard library
 #![no_main] // Disable all Rust-level entry points
+#![feature(c_variadic)] // Needed by `cprint::cprintf`
+#![feature(custom_test_frameworks)] // `cargo test` without the standard library
+#![test_runner(crate::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
 // Module declarations
+mod banner;
+mod build_info;
+mod cp437;
+mod cprint;
+mod layout;
+mod memory_map;
+mod panic;
+mod pit;
+mod platform;
+mod ramfs;
+mod rtc;
+mod selftest;
+#[cfg(test)]
+mod testing;
+mod vga;
 mod vga_buffer;
 
+use banner::BootBanner;
+use bootloader::BootInfo;
 use core::panic::PanicInfo;
 
+/// The character code the boot logo is stored in
+const LOGO_CHARACTER: u8 = 0x01;
+
+/// The boot logo bitmap (a diamond), one byte per scan line
+const LOGO_GLYPH: [u8; 16] = [
+    0x00, 0x00, 0x18, 0x3c, 0x7e, 0xff, 0xe7, 0xc3,
+    0xe7, 0xff, 0x7e, 0x3c, 0x18, 0x00, 0x00, 0x00,
+];
+
 /// The kernel entry point
 ///
 /// STUDY NOTE: This function is called by the bootloader after setting up
 /// a 64-bit environment. The `_start` name is the default entry point name
 /// that linkers look for. We use `no_mangle` to prevent Rust from changing
 /// the function name during compilation (name mangling).
+///
+/// STUDY NOTE: The bootloader passes a pointer to its `BootInfo` (with the
+/// physical memory map) as the first argument. Nothing checks that our
+/// signature matches; bootloader's `entry_point!` macro would, at the cost
+/// of hiding `_start` behind a macro.
 #[unsafe(no_mangle)]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
     // STUDY NOTE: Instead of writing directly to the VGA buffer memory,
     // we now use our abstraction layer which provides:
     // - Type safety
@@ -26,8 +61,22 @@ pub extern "C" fn _start() -> ! {
     // - Proper volatile writes
     // - Color support
 
+    vga_buffer::init();
+    pit::init();
+
+    #[cfg(test)]
+    test_main();
+
+    // STUDY NOTE: Character 0x01 is normally a smiley face. We replace its
+    // font bitmap with our own logo and print it in front of the banner.
+    vga::set_glyph(LOGO_CHARACTER, LOGO_GLYPH);
+    {
+        let mut writer = vga_buffer::WRITER.lock();
+        writer.write_byte(LOGO_CHARACTER);
+        writer.write_rainbow(" Blog OS\n");
+    }
     println!("Hello World{}", "!");
-    println!("Welcome to Blog OS");
+    vga_buffer::type_out("Welcome to Blog OS\n", 30);
     println!();
 
     // Demonstrate different types of output
@@ -35,10 +84,34 @@ pub extern "C" fn _start() -> ! {
     println!("Booleans: {} {}", true, false);
 
     // Show some system information
+    let boot_time = rtc::read_datetime();
+    let boot_time: &dyn core::fmt::Display = match &boot_time {
+        Some(time) => time,
+        None => &"time unavailable",
+    };
+    {
+        let mut writer = vga_buffer::WRITER.lock();
+        let (width, height) = (writer.width(), writer.height());
+        BootBanner::new()
+            .title(concat!("Blog OS v", env!("CARGO_PKG_VERSION")))
+            .section("System Information")
+            .kv("Commit", &build_info::GIT_HASH)
+            .kv("Built", &build_info::BUILD_TIME)
+            .kv("VGA Buffer", &format_args!("0x{:X}", 0xb8000))
+            .kv("Screen Size", &format_args!("{}x{} characters", width, height))
+            .kv("Boot Time", boot_time)
+            .kv("Platform", &format_args!("{:?}", platform::detect()))
+            .render(&mut writer);
+    }
+
     println!();
-    println!("=== System Information ===");
-    println!("VGA Buffer Address: 0x{:X}", 0xb8000);
-    println!("Screen Size: {}x{} characters", 80, 25);
+    println!("=== Memory Map ===");
+    memory_map::render_memory_map(&mut vga_buffer::WRITER.lock(), &boot_info.memory_map);
+
+    // Check that each subsystem works before we rely on it
+    println!();
+    println!("=== Self-Test ===");
+    selftest::run();
 
     // Test scrolling by printing many lines
     println!();
@@ -61,21 +134,50 @@ pub extern "C" fn _start() -> ! {
         // - Schedule processes
         // - Manage resources
         // For now, we just loop forever
+
+        // STUDY NOTE: spin_loop emits the `pause` instruction, which tells
+        // the CPU we're busy-waiting. This saves power and lets the other
+        // hyperthread of the core run faster while we spin.
+        core::hint::spin_loop();
+    }
+}
+
+/// Runs every `#[test_case]` function, then exits QEMU
+///
+/// STUDY NOTE: Under `cargo test`, the compiler collects the `#[test_case]`
+/// functions (sorted by name) and `test_main` passes them to this runner.
+/// A failing test panics; the panic handler shows the red panic screen and
+/// exits QEMU with a failure code. `_start` calls `vga_buffer::init` before
+/// `test_main`, since printing before it is caught by a debug assertion.
+/// Each test runs through `testing::Testable`, which also runs the cleanups
+/// the test registered with `testing::at_test_exit`.
+#[cfg(test)]
+fn test_runner(tests: &[&dyn testing::Testable]) {
+    panic::set_panic_behavior(panic::PanicBehavior::ExitQemu);
+    for test in tests {
+        vga_buffer::reset_writer();
+        test.run();
     }
+    vga_buffer::reset_writer();
+    println!("{} tests passed", tests.len());
+    panic::exit_qemu(panic::QEMU_EXIT_SUCCESS);
 }
 
 /// This function is called on panic
 ///
 /// STUDY NOTE: When something goes wrong in the kernel (array out of bounds,
 /// assertion failure, explicit panic, etc.), this handler is called.
-/// We print the panic information to help with debugging.
+/// We turn the whole screen red and show the panic information on it to
+/// help with debugging. What happens afterwards (halt, reboot or exiting
+/// QEMU) is chosen with `panic::set_panic_behavior`. Under `cargo test`, a
+/// panic inside a test shows which test failed instead.
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("\n=== KERNEL PANIC ===");
-    println!("{}", info);
-    println!("===================");
-
-    // Halt the CPU by looping forever
-    // In a real OS, we might try to save state or reboot
-    loop {}
+    #[cfg(test)]
+    if let Some(test) = testing::current_test() {
+        vga_buffer::show_test_failure_screen(info, test);
+        panic::finish()
+    }
+    vga_buffer::show_panic_screen(info);
+    panic::finish()
 }
@@ -0,0 +1,169 @@
+// Memory Map Heatmap
+// ==================
+// This module draws the physical memory map the bootloader hands to
+// `_start` as a row of colored blocks, so the memory layout can be seen at a
+// glance instead of read from a list of addresses.
+//
+// Study Notes:
+// - Each cell stands for an equal share of the address range from the lowest
+//   to the highest region, so big regions get many cells
+// - There are usually more regions than cells, and most of them are tiny; a
+//   cell is colored by the kind of memory that covers most of it
+// - The bootloader knows many region types; we group them into a few colors
+//   that are easy to tell apart
+
+use crate::vga_buffer::{Color, Writer, ascii_or};
+use bootloader::bootinfo::{MemoryRegion, MemoryRegionType};
+use core::fmt::Write;
+
+/// The full block glyph in code page 437, or `#` with `ascii-strict`
+const BLOCK: u8 = ascii_or(b'#', 0xdb);
+
+/// A group of memory region types that share a color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Free for the kernel to use
+    Usable,
+    /// Taken by the kernel, its stack, page tables or the bootloader
+    InUse,
+    /// ACPI tables and ACPI firmware memory
+    Acpi,
+    /// Reserved by the hardware, or bad memory
+    Reserved,
+}
+
+/// Every kind, in the order of the legend
+const KINDS: [Kind; 4] = [Kind::Usable, Kind::InUse, Kind::Acpi, Kind::Reserved];
+
+impl Kind {
+    /// Groups a region type; `None` for empty regions, which aren't drawn
+    fn of(region_type: MemoryRegionType) -> Option<Kind> {
+        match region_type {
+            MemoryRegionType::Usable => Some(Kind::Usable),
+            MemoryRegionType::AcpiReclaimable | MemoryRegionType::AcpiNvs => Some(Kind::Acpi),
+            MemoryRegionType::Reserved
+            | MemoryRegionType::BadMemory
+            | MemoryRegionType::FrameZero => Some(Kind::Reserved),
+            MemoryRegionType::Empty => None,
+            _ => Some(Kind::InUse),
+        }
+    }
+
+    /// The color cells of this kind are drawn in
+    fn color(self) -> Color {
+        match self {
+            Kind::Usable => Color::Green,
+            Kind::InUse => Color::Cyan,
+            Kind::Acpi => Color::Yellow,
+            Kind::Reserved => Color::Red,
+        }
+    }
+
+    /// The name shown in the legend
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Usable => "usable",
+            Kind::InUse => "in use",
+            Kind::Acpi => "ACPI",
+            Kind::Reserved => "reserved",
+        }
+    }
+}
+
+/// Draws `regions` as one row of colored blocks followed by a legend
+///
+/// The row is as wide as a line of text (see `Writer::line_width`). Gaps
+/// that no region covers are shown as dark gray dots.
+pub fn render_memory_map(writer: &mut Writer, regions: &[MemoryRegion]) {
+    let Some((start, end)) = address_range(regions) else {
+        writer.write_string("(empty memory map)\n");
+        return;
+    };
+    let cells = writer.line_width();
+    for cell in 0..cells {
+        let (cell_start, cell_end) = cell_range(start, end, cell, cells);
+        match dominant_kind(regions, cell_start, cell_end) {
+            Some(kind) => writer.with_color(kind.color(), Color::Black, |w| w.write_byte(BLOCK)),
+            None => writer.with_color(Color::DarkGray, Color::Black, |w| w.write_byte(b'.')),
+        }
+    }
+    writer.write_byte(b'\n');
+
+    for kind in KINDS {
+        writer.with_color(kind.color(), Color::Black, |w| w.write_byte(BLOCK));
+        write!(writer, " {}  ", kind.name()).unwrap();
+    }
+    let kib_per_cell = (end - start) / cells as u64 / 1024;
+    writeln!(writer, "(1 cell = {} KiB)", kib_per_cell.max(1)).unwrap();
+}
+
+/// Returns the addresses from the start of the lowest region to the end of
+/// the highest one, or `None` if there is nothing to draw
+fn address_range(regions: &[MemoryRegion]) -> Option<(u64, u64)> {
+    let mut drawn = regions.iter().filter(|region| Kind::of(region.region_type).is_some());
+    let first = drawn.next()?;
+    let range = drawn.fold((first.range.start_addr(), first.range.end_addr()), |(start, end), region| {
+        (start.min(region.range.start_addr()), end.max(region.range.end_addr()))
+    });
+    Some(range)
+}
+
+/// Returns the addresses covered by cell `cell` of `cells`
+///
+/// STUDY NOTE: The multiplication is done in u128, since the size of the
+/// address range times the cell count can overflow a u64.
+fn cell_range(start: u64, end: u64, cell: usize, cells: usize) -> (u64, u64) {
+    let size = u128::from(end - start);
+    let offset = |cell: usize| (size * cell as u128 / cells as u128) as u64;
+    (start + offset(cell), start + offset(cell + 1))
+}
+
+/// Returns the kind of memory that covers most of `start..end`
+fn dominant_kind(regions: &[MemoryRegion], start: u64, end: u64) -> Option<Kind> {
+    let mut covered = [0u64; KINDS.len()];
+    for region in regions {
+        let Some(kind) = Kind::of(region.region_type) else {
+            continue;
+        };
+        let overlap_start = region.range.start_addr().max(start);
+        let overlap_end = region.range.end_addr().min(end);
+        if overlap_end > overlap_start {
+            covered[kind as usize] += overlap_end - overlap_start;
+        }
+    }
+    KINDS
+        .iter()
+        .zip(covered)
+        .filter(|&(_, bytes)| bytes > 0)
+        .max_by_key(|&(_, bytes)| bytes)
+        .map(|(&kind, _)| kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootloader::bootinfo::FrameRange;
+
+    fn region(start: u64, end: u64, region_type: MemoryRegionType) -> MemoryRegion {
+        MemoryRegion {
+            range: FrameRange::new(start, end),
+            region_type,
+        }
+    }
+
+    #[test_case]
+    fn test_dominant_kind() {
+        let regions = [
+            region(0x0000, 0x1000, MemoryRegionType::FrameZero),
+            region(0x1000, 0x9000, MemoryRegionType::Usable),
+            region(0x9000, 0xa000, MemoryRegionType::Kernel),
+            region(0xb000, 0xc000, MemoryRegionType::AcpiNvs),
+        ];
+        assert_eq!(address_range(&regions), Some((0x0000, 0xc000)));
+        // A cell is colored by the majority, even if other kinds are in it
+        assert_eq!(dominant_kind(&regions, 0x0000, 0x4000), Some(Kind::Usable));
+        assert_eq!(dominant_kind(&regions, 0x9000, 0xb000), Some(Kind::InUse));
+        assert_eq!(dominant_kind(&regions, 0xa000, 0xb000), None);
+        assert_eq!(cell_range(0x0000, 0xc000, 2, 3), (0x8000, 0xc000));
+    }
+}
@@ -0,0 +1,124 @@
+// Panic Behavior
+// ==============
+// This module decides what the kernel does after the panic handler has shown
+// the panic message: halt (to read the message), reboot (for a kiosk that
+// should come back on its own) or exit QEMU with a failure code (for tests).
+//
+// Study Notes:
+// - There is only one panic handler; the behavior is a runtime switch, so
+//   test kernels and normal kernels don't need separate handlers
+// - The switch is an atomic, so the panic handler can read it without taking
+//   a lock that the panicking code might hold
+
+use crate::{pit, platform};
+use core::sync::atomic::{AtomicU8, Ordering};
+use x86_64::instructions::port::Port;
+
+/// The port of QEMU's `isa-debug-exit` device (see `package.metadata.bootimage`)
+const QEMU_EXIT_PORT: u16 = 0xf4;
+/// Success exit code: QEMU exits with status `(code << 1) | 1`, i.e. 33
+#[allow(dead_code)]
+pub const QEMU_EXIT_SUCCESS: u32 = 0x10;
+/// Failure exit code: QEMU exits with status 35
+pub const QEMU_EXIT_FAILED: u32 = 0x11;
+/// Status and command port of the 8042 (PS/2) keyboard controller
+const KEYBOARD_CONTROLLER: u16 = 0x64;
+/// Status bit: the controller hasn't read the last command yet
+const KEYBOARD_CONTROLLER_BUSY: u8 = 0x02;
+/// Command: pulse the CPU reset line
+const KEYBOARD_CONTROLLER_RESET: u8 = 0xfe;
+/// The pitch and length of the beep that announces a panic
+const PANIC_BEEP_HZ: u32 = 1000;
+const PANIC_BEEP_MS: u64 = 200;
+
+/// What the kernel does after showing the panic message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum PanicBehavior {
+    /// Stop and keep the message on screen (the default)
+    Halt = 0,
+    /// Restart the machine
+    Reboot = 1,
+    /// Exit QEMU with a failure code; halts on other platforms
+    ExitQemu = 2,
+}
+
+static PANIC_BEHAVIOR: AtomicU8 = AtomicU8::new(PanicBehavior::Halt as u8);
+
+/// Chooses what the kernel does after a panic
+///
+/// STUDY NOTE: Call this as early as possible during boot: a panic that
+/// happens before still uses the previous behavior (halting by default).
+#[allow(dead_code)]
+pub fn set_panic_behavior(behavior: PanicBehavior) {
+    PANIC_BEHAVIOR.store(behavior as u8, Ordering::Relaxed);
+}
+
+/// Returns what the kernel does after a panic
+pub fn panic_behavior() -> PanicBehavior {
+    match PANIC_BEHAVIOR.load(Ordering::Relaxed) {
+        1 => PanicBehavior::Reboot,
+        2 => PanicBehavior::ExitQemu,
+        _ => PanicBehavior::Halt,
+    }
+}
+
+/// Beeps and carries out the panic behavior; called by the panic handler
+///
+/// STUDY NOTE: The beep makes a crash noticeable even when nobody watches
+/// the screen, e.g. on a kiosk that is about to reboot.
+pub fn finish() -> ! {
+    pit::beep(PANIC_BEEP_HZ, PANIC_BEEP_MS);
+    match panic_behavior() {
+        PanicBehavior::Halt => {}
+        PanicBehavior::Reboot => reboot(),
+        PanicBehavior::ExitQemu => exit_qemu(QEMU_EXIT_FAILED),
+    }
+    halt()
+}
+
+/// Exits QEMU with the given code; returns if we don't run under QEMU
+pub fn exit_qemu(code: u32) {
+    if platform::is_qemu() {
+        // STUDY NOTE: Without the isa-debug-exit device, nothing listens
+        // on this port and the call simply returns.
+        unsafe { Port::<u32>::new(QEMU_EXIT_PORT).write(code) };
+    }
+}
+
+/// Asks the keyboard controller to reset the CPU
+fn reboot() {
+    unsafe {
+        let mut controller = Port::<u8>::new(KEYBOARD_CONTROLLER);
+        while controller.read() & KEYBOARD_CONTROLLER_BUSY != 0 {
+            core::hint::spin_loop();
+        }
+        controller.write(KEYBOARD_CONTROLLER_RESET);
+    }
+}
+
+/// Halts the CPU by looping forever
+fn halt() -> ! {
+    // In a real OS, we might try to save state first
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A panic would end the test run, so the panic path itself is tested by
+    // the separate tests/should_panic.rs kernel
+    #[test_case]
+    fn test_set_panic_behavior() {
+        // The test runner relies on ExitQemu, so put it back afterwards
+        crate::testing::at_test_exit(|| set_panic_behavior(PanicBehavior::ExitQemu));
+        set_panic_behavior(PanicBehavior::Reboot);
+        assert_eq!(panic_behavior(), PanicBehavior::Reboot);
+        set_panic_behavior(PanicBehavior::Halt);
+        assert_eq!(panic_behavior(), PanicBehavior::Halt);
+    }
+}
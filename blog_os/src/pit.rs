@@ -0,0 +1,230 @@
+// Programmable Interval Timer
+// ===========================
+// This module uses channel 2 of the PIT (the 8253/8254 timer chip) to
+// calibrate a busy-wait delay, so code can wait for a number of microseconds
+// without a timer interrupt, and to beep the PC speaker.
+//
+// Study Notes:
+// - The PIT counts down at a fixed 1.193182 MHz, whatever the CPU speed
+// - Channel 2 is the one wired to the PC speaker. Unlike channel 0, its
+//   output can be read back through bit 5 of port 0x61, so it can be
+//   polled without any interrupt handler
+// - The CPU's time stamp counter (TSC, read with `rdtsc`) ticks much faster
+//   than the PIT but at a rate we don't know. Counting TSC ticks during one
+//   known PIT interval tells us that rate; after that, delays only need the
+//   TSC
+// - In mode 3 the channel outputs a square wave at PIT_FREQUENCY / count Hz;
+//   with the speaker bit of port 0x61 set, the speaker plays that tone
+
+use core::arch::x86_64::_rdtsc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+/// The frequency the PIT counts down at, in Hz
+const PIT_FREQUENCY: u64 = 1_193_182;
+
+const PIT_CHANNEL2_PORT: u16 = 0x42;
+const PIT_COMMAND_PORT: u16 = 0x43;
+/// Port B of the 8255 chip: the channel 2 gate, the speaker and the output
+const SPEAKER_CONTROL_PORT: u16 = 0x61;
+
+/// Command: channel 2, low then high byte, mode 0 (one-shot), binary
+const CHANNEL2_ONE_SHOT: u8 = 0b1011_0000;
+/// Command: channel 2, low then high byte, mode 3 (square wave), binary
+const CHANNEL2_SQUARE_WAVE: u8 = 0b1011_0110;
+/// Command: latch the current count of channel 2 for reading
+#[cfg(test)]
+const CHANNEL2_LATCH: u8 = 0b1000_0000;
+/// Port 0x61: channel 2 counts while this (gate) bit is set
+const CHANNEL2_GATE: u8 = 0x01;
+/// Port 0x61: the speaker follows channel 2 while this bit is set
+const SPEAKER_DATA: u8 = 0x02;
+/// Port 0x61: the output of channel 2; in mode 0 it is set once the count
+/// reaches zero
+const CHANNEL2_OUTPUT: u8 = 0x20;
+
+/// How long the calibration measures, in microseconds
+const CALIBRATION_US: u64 = 10_000;
+/// The PIT count that lasts `CALIBRATION_US`
+const CALIBRATION_COUNT: u16 = (PIT_FREQUENCY * CALIBRATION_US / 1_000_000) as u16;
+/// How often we check the channel 2 output before giving up on the PIT
+///
+/// STUDY NOTE: A port read takes around a microsecond, so this waits far
+/// longer than the 10 ms the count lasts. It only matters without a PIT.
+const MAX_CALIBRATION_POLLS: u32 = 10_000_000;
+/// The TSC rate assumed if calibration fails: that of a 4 GHz CPU, which is
+/// faster than most, so delays rather get longer than shorter
+const FALLBACK_TSC_PER_MS: u64 = 4_000_000;
+
+/// TSC ticks per millisecond, or 0 before calibration
+static TSC_PER_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Calibrates the delay loop
+///
+/// STUDY NOTE: This busy-waits for 10 ms. Calling it early in `_start`
+/// puts that wait at a known point during boot; otherwise the first delay
+/// calibrates and takes 10 ms longer.
+pub fn init() {
+    TSC_PER_MS.store(
+        calibrate().unwrap_or(FALLBACK_TSC_PER_MS),
+        Ordering::Relaxed,
+    );
+}
+
+/// Busy-waits for at least `us` microseconds
+///
+/// STUDY NOTE: Accuracy limits:
+/// - Calibration measures one 10 ms PIT interval, and reading the PIT takes
+///   about a microsecond, so the rate is off by about 0.01%
+/// - Reading the TSC takes some time too, so very short delays (a few
+///   microseconds, more under emulation) take longer than asked
+/// - The TSC must tick at a constant rate; very old CPUs slow it down in
+///   power saving states, which makes delays longer
+/// - Once interrupts are enabled, a handler running during the delay makes
+///   it longer; the delay is a minimum, not an exact time
+pub fn delay_us(us: u64) {
+    let ticks = us.saturating_mul(tsc_per_ms()) / 1000;
+    let start = rdtsc();
+    while rdtsc().wrapping_sub(start) < ticks {
+        core::hint::spin_loop();
+    }
+}
+
+/// Busy-waits for at least `ms` milliseconds (see `delay_us`)
+pub fn delay_ms(ms: u64) {
+    delay_us(ms.saturating_mul(1000));
+}
+
+/// Plays a tone of `frequency_hz` on the PC speaker for `duration_ms`
+///
+/// Frequencies are limited to what the PIT can divide its clock into, about
+/// 19 Hz to 1.19 MHz (people hear roughly 20 Hz to 20 kHz); 0 Hz stays
+/// silent. Port 0x61 gets its original value back afterwards, so the
+/// speaker is off again. QEMU only plays the tone with `-audiodev` and
+/// `-machine pcspk-audiodev=...`.
+///
+/// STUDY NOTE: The wait is a busy-wait (see `delay_us`), so nothing else
+/// runs while the speaker beeps.
+pub fn beep(frequency_hz: u32, duration_ms: u64) {
+    if frequency_hz == 0 {
+        delay_ms(duration_ms);
+        return;
+    }
+    let divisor = (PIT_FREQUENCY / u64::from(frequency_hz)).clamp(1, u64::from(u16::MAX));
+    let mut control = Port::<u8>::new(SPEAKER_CONTROL_PORT);
+    let original = interrupts::without_interrupts(|| unsafe {
+        program_channel2(CHANNEL2_SQUARE_WAVE, divisor as u16);
+        let original = control.read();
+        control.write(original | CHANNEL2_GATE | SPEAKER_DATA);
+        original
+    });
+    delay_ms(duration_ms);
+    unsafe { control.write(original) };
+}
+
+/// Returns the calibrated TSC rate, calibrating first if needed
+fn tsc_per_ms() -> u64 {
+    match TSC_PER_MS.load(Ordering::Relaxed) {
+        0 => {
+            init();
+            TSC_PER_MS.load(Ordering::Relaxed)
+        }
+        rate => rate,
+    }
+}
+
+/// Counts the TSC ticks of one `CALIBRATION_US` PIT interval and returns
+/// the TSC ticks per millisecond, or `None` if the PIT never finishes
+fn calibrate() -> Option<u64> {
+    with_channel2(|| {
+        program_channel2(CHANNEL2_ONE_SHOT, CALIBRATION_COUNT);
+        let start = rdtsc();
+        let mut polls = 0;
+        while unsafe { Port::<u8>::new(SPEAKER_CONTROL_PORT).read() } & CHANNEL2_OUTPUT == 0 {
+            polls += 1;
+            if polls == MAX_CALIBRATION_POLLS {
+                return None;
+            }
+        }
+        let ticks = u128::from(rdtsc().wrapping_sub(start));
+        // CALIBRATION_COUNT PIT ticks last CALIBRATION_COUNT / PIT_FREQUENCY
+        // seconds, slightly less than CALIBRATION_US
+        let per_ms = ticks * u128::from(PIT_FREQUENCY) / (u128::from(CALIBRATION_COUNT) * 1000);
+        Some((per_ms as u64).max(1))
+    })
+}
+
+/// Runs `f` with channel 2 gated on and the speaker off, then restores
+/// port 0x61
+fn with_channel2<R>(f: impl FnOnce() -> R) -> R {
+    interrupts::without_interrupts(|| {
+        let mut control = Port::<u8>::new(SPEAKER_CONTROL_PORT);
+        let original = unsafe { control.read() };
+        unsafe { control.write(original & !SPEAKER_DATA | CHANNEL2_GATE) };
+        let result = f();
+        unsafe { control.write(original) };
+        result
+    })
+}
+
+/// Sets the mode of channel 2 with `command` and starts it from `count`
+fn program_channel2(command: u8, count: u16) {
+    unsafe {
+        Port::<u8>::new(PIT_COMMAND_PORT).write(command);
+        let mut data = Port::<u8>::new(PIT_CHANNEL2_PORT);
+        data.write(count as u8);
+        data.write((count >> 8) as u8);
+    }
+}
+
+/// Reads the TSC
+fn rdtsc() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the current count of channel 2
+    fn read_channel2() -> u16 {
+        unsafe {
+            Port::<u8>::new(PIT_COMMAND_PORT).write(CHANNEL2_LATCH);
+            let mut data = Port::<u8>::new(PIT_CHANNEL2_PORT);
+            let low = data.read();
+            let high = data.read();
+            u16::from_le_bytes([low, high])
+        }
+    }
+
+    #[test_case]
+    fn test_delay_us_matches_the_pit() {
+        // Measure the delay with the PIT itself, which is independent of
+        // the TSC rate the delay was calibrated to
+        tsc_per_ms();
+        let elapsed = with_channel2(|| {
+            program_channel2(CHANNEL2_ONE_SHOT, u16::MAX);
+            delay_us(1000);
+            u16::MAX - read_channel2()
+        });
+        // At least the time asked for (less the one PIT tick a reading can
+        // be off by), and at most one tick of a 1 kHz timer more
+        let one_ms = PIT_FREQUENCY / 1000;
+        let elapsed = u64::from(elapsed);
+        assert!(
+            (one_ms - 1..=2 * one_ms).contains(&elapsed),
+            "delay_us(1000) took {} PIT ticks",
+            elapsed
+        );
+    }
+
+    #[test_case]
+    fn test_beep_restores_speaker_control() {
+        let mut control = Port::<u8>::new(SPEAKER_CONTROL_PORT);
+        let speaker_bits = CHANNEL2_GATE | SPEAKER_DATA;
+        let before = unsafe { control.read() } & speaker_bits;
+        beep(1000, 1);
+        assert_eq!(unsafe { control.read() } & speaker_bits, before);
+    }
+}
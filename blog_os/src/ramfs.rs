@@ -0,0 +1,52 @@
+// In-Memory Filesystem
+// ====================
+// This module provides a tiny read-only filesystem for data that is
+// compiled into the kernel image, like help texts or banners.
+//
+// Study Notes:
+// - `include_bytes!` embeds a file's contents into the binary at compile
+//   time and gives us a `&'static [u8]` pointing to it
+// - The file table is a static slice, so lookups never allocate
+// - To add a file, put it in the `assets` directory and add it to FILES
+
+/// A file embedded into the kernel image
+#[derive(Debug, Clone, Copy)]
+pub struct File {
+    pub name: &'static str,
+    pub data: &'static [u8],
+}
+
+/// All embedded files
+static FILES: &[File] = &[File {
+    name: "help.txt",
+    data: include_bytes!("../assets/help.txt"),
+}];
+
+/// Returns the contents of the file with the given name
+#[allow(dead_code)]
+pub fn open(name: &str) -> Option<&'static [u8]> {
+    FILES.iter().find(|file| file.name == name).map(|file| file.data)
+}
+
+/// Returns an iterator over all embedded files
+#[allow(dead_code)]
+pub fn list() -> impl Iterator<Item = &'static File> {
+    FILES.iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_open_embedded_file() {
+        let help = open("help.txt").unwrap();
+        assert!(help.starts_with(b"Blog OS"));
+        assert!(open("missing.txt").is_none());
+    }
+
+    #[test_case]
+    fn test_list_contains_help() {
+        assert!(list().any(|file| file.name == "help.txt"));
+    }
+}
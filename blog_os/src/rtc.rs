@@ -0,0 +1,209 @@
+// Real-Time Clock
+// ===============
+// This module reads the wall-clock date and time from the battery-backed
+// real-time clock (RTC) in the CMOS chip.
+//
+// Study Notes:
+// - CMOS registers are accessed through an index/data port pair, like the
+//   VGA registers: write the register number to 0x70, then read 0x71
+// - The RTC updates its registers once per second; while it does, the values
+//   may be inconsistent and the update-in-progress (UIP) bit is set
+// - Depending on status register B, values are stored in binary or in BCD
+//   (binary-coded decimal, e.g. 0x59 for 59) and hours in 12- or 24-hour form
+
+use core::fmt;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_DAY: u8 = 0x07;
+const RTC_MONTH: u8 = 0x08;
+const RTC_YEAR: u8 = 0x09;
+const RTC_STATUS_A: u8 = 0x0A;
+const RTC_STATUS_B: u8 = 0x0B;
+
+/// Status A: the RTC is updating its registers
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+/// Status B: values are binary instead of BCD
+const STATUS_B_BINARY: u8 = 0x04;
+/// Status B: hours are in 24-hour instead of 12-hour form
+const STATUS_B_24_HOUR: u8 = 0x02;
+/// In 12-hour form, the top bit of the hours register marks PM
+const HOUR_PM: u8 = 0x80;
+
+/// How often we check the UIP bit before giving up on the RTC
+///
+/// STUDY NOTE: An update takes about 2 ms and a port read around a
+/// microsecond, so this waits several times longer than any real update.
+/// Without a CMOS chip the bus floats and reads 0xff, which looks like an
+/// update that never ends.
+const MAX_UPDATE_WAIT: u32 = 10_000;
+/// How often we read all registers while waiting for two reads to agree
+const MAX_READS: u32 = 5;
+
+/// A calendar date and time of day as reported by the RTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    /// Formats as `YYYY-MM-DD HH:MM:SS`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// The RTC registers exactly as read from CMOS
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct RawTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+/// Reads a CMOS register
+fn read_cmos(register: u8) -> u8 {
+    // STUDY NOTE: Bit 7 of the index port disables NMIs. We leave it clear,
+    // so NMIs stay enabled while we read.
+    unsafe {
+        Port::<u8>::new(CMOS_INDEX).write(register);
+        Port::<u8>::new(CMOS_DATA).read()
+    }
+}
+
+/// Waits until the RTC is not in the middle of an update
+///
+/// Returns false if the update doesn't finish within `MAX_UPDATE_WAIT` reads.
+fn wait_for_update() -> bool {
+    for _ in 0..MAX_UPDATE_WAIT {
+        if read_cmos(RTC_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS == 0 {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
+}
+
+/// Reads all time registers once the RTC is not in the middle of an update
+fn read_raw() -> Option<RawTime> {
+    if !wait_for_update() {
+        return None;
+    }
+    Some(RawTime {
+        second: read_cmos(RTC_SECONDS),
+        minute: read_cmos(RTC_MINUTES),
+        hour: read_cmos(RTC_HOURS),
+        day: read_cmos(RTC_DAY),
+        month: read_cmos(RTC_MONTH),
+        year: read_cmos(RTC_YEAR),
+    })
+}
+
+/// Converts a BCD byte (e.g. 0x59) to binary (59)
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+/// Decodes raw registers according to the format bits of status register B
+fn decode(raw: RawTime, status_b: u8) -> DateTime {
+    let pm = raw.hour & HOUR_PM != 0;
+    let mut raw = RawTime { hour: raw.hour & !HOUR_PM, ..raw };
+    if status_b & STATUS_B_BINARY == 0 {
+        raw = RawTime {
+            second: bcd_to_binary(raw.second),
+            minute: bcd_to_binary(raw.minute),
+            hour: bcd_to_binary(raw.hour),
+            day: bcd_to_binary(raw.day),
+            month: bcd_to_binary(raw.month),
+            year: bcd_to_binary(raw.year),
+        };
+    }
+    // STUDY NOTE: In 12-hour form midnight is 12 AM and noon is 12 PM
+    if status_b & STATUS_B_24_HOUR == 0 {
+        raw.hour %= 12;
+        if pm {
+            raw.hour += 12;
+        }
+    }
+    DateTime {
+        // STUDY NOTE: The century register's location is given by an ACPI
+        // table we don't parse yet, so we assume the 21st century.
+        year: 2000 + u16::from(raw.year),
+        month: raw.month,
+        day: raw.day,
+        hour: raw.hour,
+        minute: raw.minute,
+        second: raw.second,
+    }
+}
+
+/// Reads the current date and time from the RTC
+///
+/// Returns `None` if the RTC doesn't answer (no CMOS chip, or an update that
+/// never finishes), so a broken clock can't hang the boot.
+///
+/// STUDY NOTE: Waiting for the UIP bit to clear isn't enough on its own: an
+/// update may start right after we checked. So we read the registers until
+/// two reads in a row agree.
+pub fn read_datetime() -> Option<DateTime> {
+    interrupts::without_interrupts(|| {
+        let mut raw = read_raw()?;
+        for _ in 0..MAX_READS {
+            let again = read_raw()?;
+            if again == raw {
+                return Some(decode(raw, read_cmos(RTC_STATUS_B)));
+            }
+            raw = again;
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOON: RawTime = RawTime {
+        second: 0x05,
+        minute: 0x59,
+        hour: 0x12,
+        day: 0x31,
+        month: 0x12,
+        year: 0x24,
+    };
+
+    #[test_case]
+    fn test_decode_bcd_24_hour() {
+        let time = decode(NOON, STATUS_B_24_HOUR);
+        assert_eq!(time.year, 2024);
+        assert_eq!(time.day, 31);
+        assert_eq!(time.hour, 12);
+        assert_eq!(time.minute, 59);
+    }
+
+    #[test_case]
+    fn test_decode_binary_12_hour() {
+        let midnight = RawTime { hour: 12, ..NOON };
+        assert_eq!(decode(midnight, STATUS_B_BINARY).hour, 0);
+        let one_pm = RawTime { hour: HOUR_PM | 1, ..NOON };
+        assert_eq!(decode(one_pm, STATUS_B_BINARY).hour, 13);
+    }
+}
@@ -0,0 +1,59 @@
+// Boot-Time Self-Test
+// ===================
+// This module runs a quick check of each subsystem after boot and prints a
+// colored checklist, so a partially broken build is visible immediately.
+//
+// Study Notes:
+// - Each check is a plain function returning whether the subsystem works
+// - Checks must leave the system as they found it (no side effects)
+// - New subsystems add a line to the CHECKS table below
+
+use crate::vga;
+use crate::vga_buffer::{Color, WRITER};
+use core::fmt::Write;
+
+/// A named check that returns true if the subsystem works
+struct Check {
+    name: &'static str,
+    run: fn() -> bool,
+}
+
+/// All checks, in the order they are run and printed
+const CHECKS: &[Check] = &[
+    Check {
+        name: "VGA buffer",
+        run: || WRITER.lock().verify_buffer(),
+    },
+    Check {
+        name: "VGA registers",
+        run: vga::verify_registers,
+    },
+];
+
+/// Runs all checks and prints an `[OK]`/`[FAIL]` line for each of them,
+/// followed by a pass/fail summary
+///
+/// Returns true if every check passed.
+pub fn run() -> bool {
+    let mut passed = 0;
+    for check in CHECKS {
+        // STUDY NOTE: The check runs before we take the WRITER lock, since
+        // checks may need the lock themselves (spin locks aren't reentrant).
+        let ok = (check.run)();
+        let mut writer = WRITER.lock();
+        if ok {
+            passed += 1;
+            writer.with_color(Color::LightGreen, Color::Black, |w| w.write_string("[OK]  "));
+        } else {
+            writer.with_color(Color::LightRed, Color::Black, |w| w.write_string("[FAIL]"));
+        }
+        writeln!(writer, " {}", check.name).unwrap();
+    }
+
+    let failed = CHECKS.len() - passed;
+    let color = if failed == 0 { Color::LightGreen } else { Color::LightRed };
+    WRITER.lock().with_color(color, Color::Black, |w| {
+        writeln!(w, "Self-test: {} passed, {} failed", passed, failed).unwrap();
+    });
+    failed == 0
+}
@@ -0,0 +1,111 @@
+// Test Support
+// ============
+// This module holds what the test runner in main.rs needs besides the tests
+// themselves: the `Testable` wrapper every `#[test_case]` runs through (which
+// records the name of the running test for the failure screen), and a
+// registry of cleanup functions that undo a test's changes to global state.
+//
+// Study Notes:
+// - The runner already resets the WRITER before every test; other global
+//   state (panic behavior, output switch, VGA registers) is put back by
+//   cleanups the test registers itself
+// - There is no heap, so the registry is a fixed-size array of function
+//   pointers; a closure that captures nothing coerces to `fn()`
+// - A failing test panics, and without unwinding the panic ends the whole
+//   run, so cleanups only run after tests that pass
+
+use spin::Mutex;
+
+/// The most cleanup functions one test can register
+const MAX_CLEANUPS: usize = 8;
+
+/// Slots for cleanup functions; `None` marks a free slot
+type Cleanups = [Option<fn()>; MAX_CLEANUPS];
+
+/// Cleanups registered by the running test, in order of registration
+static CLEANUPS: Mutex<Cleanups> = Mutex::new([None; MAX_CLEANUPS]);
+
+/// The name of the test that is running, if any
+static CURRENT_TEST: Mutex<Option<&'static str>> = Mutex::new(None);
+
+/// A test the runner can run
+///
+/// STUDY NOTE: `#[test_case]` functions are plain functions, so this is
+/// implemented for everything that implements `Fn()`. Going through the
+/// trait gives the runner one place to hook into every test, and the type
+/// parameter gives us the test's name through `core::any::type_name`.
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        *CURRENT_TEST.lock() = Some(core::any::type_name::<T>());
+        self();
+        run_cleanups();
+        *CURRENT_TEST.lock() = None;
+    }
+}
+
+/// Returns the full path of the running test, like
+/// `blog_os::vga_buffer::tests::test_init_twice`
+///
+/// The panic handler calls this, so it never waits for the lock.
+pub fn current_test() -> Option<&'static str> {
+    CURRENT_TEST.try_lock().and_then(|test| *test)
+}
+
+/// Registers a function the runner calls after the current test
+///
+/// Cleanups run in reverse order of registration, like C's `atexit`
+/// handlers, so a test can undo its changes step by step. Panics if the
+/// test registers more than `MAX_CLEANUPS`.
+pub fn at_test_exit(cleanup: fn()) {
+    let mut cleanups = CLEANUPS.lock();
+    let slot = cleanups
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .expect("too many test cleanups registered");
+    *slot = Some(cleanup);
+}
+
+/// Runs and unregisters the cleanups, newest first
+fn run_cleanups() {
+    // The lock is released before each call, so a cleanup may register
+    // another one or use anything that takes locks itself
+    while let Some(cleanup) = {
+        let mut cleanups = CLEANUPS.lock();
+        cleanups.iter_mut().rev().find_map(|slot| slot.take())
+    } {
+        cleanup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test_case]
+    fn test_current_test_is_recorded() {
+        let test = current_test().unwrap_or("");
+        assert!(test.ends_with("::tests::test_current_test_is_recorded"));
+    }
+
+    #[test_case]
+    fn test_cleanups_run_newest_first() {
+        CALLS.store(0, Ordering::Relaxed);
+        // Each cleanup appends its digit, so the order is visible
+        at_test_exit(|| {
+            CALLS.store(CALLS.load(Ordering::Relaxed) * 10 + 1, Ordering::Relaxed);
+        });
+        at_test_exit(|| {
+            CALLS.store(CALLS.load(Ordering::Relaxed) * 10 + 2, Ordering::Relaxed);
+        });
+        run_cleanups();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 21);
+        assert!(CLEANUPS.lock().iter().all(|slot| slot.is_none()));
+    }
+}
@@ -0,0 +1,375 @@
+// VGA Register Programming
+// ========================
+// This module talks to the VGA hardware registers through I/O ports to
+// change how text mode is displayed. The `vga_buffer` module only writes
+// characters into video memory; everything here changes how the hardware
+// interprets that memory.
+//
+// Study Notes:
+// - VGA registers are accessed through index/data port pairs: you write the
+//   register index to the index port, then read or write the data port
+// - The sequencer (0x3C4/0x3C5) controls which memory planes are written
+// - The graphics controller (0x3CE/0x3CF) controls how memory is mapped
+// - The CRT controller (0x3D4/0x3D5) controls timing, scan lines and cursor
+// - In text mode, plane 0 holds characters, plane 1 attributes and plane 2
+//   the font bitmaps (32 bytes reserved per glyph)
+
+use crate::vga_buffer::WRITER;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+// =============================================================================
+// PORTS AND REGISTERS
+// =============================================================================
+
+const SEQUENCER_INDEX: u16 = 0x3C4;
+const SEQUENCER_DATA: u16 = 0x3C5;
+const GRAPHICS_INDEX: u16 = 0x3CE;
+const GRAPHICS_DATA: u16 = 0x3CF;
+const ATTRIBUTE_INDEX_DATA: u16 = 0x3C0;
+const ATTRIBUTE_DATA_READ: u16 = 0x3C1;
+const INPUT_STATUS_1: u16 = 0x3DA;
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+
+const SEQUENCER_MAP_MASK: u8 = 0x02;
+const SEQUENCER_MEMORY_MODE: u8 = 0x04;
+const GRAPHICS_READ_MAP: u8 = 0x04;
+const GRAPHICS_MODE: u8 = 0x05;
+const GRAPHICS_MISC: u8 = 0x06;
+const ATTRIBUTE_MODE_CONTROL: u8 = 0x10;
+const CRTC_MAX_SCAN_LINE: u8 = 0x09;
+const CRTC_CURSOR_START: u8 = 0x0A;
+const CRTC_CURSOR_END: u8 = 0x0B;
+const CRTC_CURSOR_LOCATION_HIGH: u8 = 0x0E;
+const CRTC_CURSOR_LOCATION_LOW: u8 = 0x0F;
+
+/// Reads a register behind an index/data port pair
+unsafe fn read_register(index_port: u16, data_port: u16, index: u8) -> u8 {
+    unsafe {
+        Port::<u8>::new(index_port).write(index);
+        Port::<u8>::new(data_port).read()
+    }
+}
+
+/// Writes a register behind an index/data port pair
+unsafe fn write_register(index_port: u16, data_port: u16, index: u8, value: u8) {
+    unsafe {
+        Port::<u8>::new(index_port).write(index);
+        Port::<u8>::new(data_port).write(value);
+    }
+}
+
+/// Replaces the bits selected by `mask` in a register with `value`
+unsafe fn update_register(index_port: u16, data_port: u16, index: u8, mask: u8, value: u8) {
+    unsafe {
+        let old = read_register(index_port, data_port, index);
+        write_register(index_port, data_port, index, (old & !mask) | (value & mask));
+    }
+}
+
+// =============================================================================
+// FONT MEMORY ACCESS
+// =============================================================================
+
+/// Physical address plane 2 is mapped at while font memory is accessible
+/// STUDY NOTE: The bootloader identity-maps 0xa0000..0xc0000, so this
+/// address is valid without any further page table setup.
+const FONT_MEMORY: usize = 0xa0000;
+/// Bytes reserved per glyph in font memory (only the first rows are used)
+const GLYPH_SLOT_SIZE: usize = 32;
+/// Number of glyphs in a VGA font
+const GLYPH_COUNT: usize = 256;
+/// Height in scan lines of the font the firmware loads for 80x25 mode
+const FONT_HEIGHT_80X25: usize = 16;
+/// Height in scan lines of the font used for 80x50 mode
+const FONT_HEIGHT_80X50: usize = 8;
+
+/// Copy of the firmware's 8x16 font, taken before it is first replaced
+static SAVED_FONT: Mutex<Option<[u8; GLYPH_COUNT * FONT_HEIGHT_80X25]>> = Mutex::new(None);
+
+/// Runs `f` with plane 2 (the font memory) mapped at `FONT_MEMORY`
+///
+/// STUDY NOTE: Normally the CPU sees planes 0 and 1 interleaved at 0xb8000
+/// (odd/even mode). To reach the font we temporarily select plane 2,
+/// switch to sequential addressing and map video memory at 0xa0000.
+/// Every register we touch is saved first and restored afterwards, and
+/// interrupts are disabled so nothing can print while the text buffer is
+/// not mapped.
+unsafe fn with_font_memory<R>(f: impl FnOnce(*mut u8) -> R) -> R {
+    interrupts::without_interrupts(|| unsafe {
+        let map_mask = read_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK);
+        let memory_mode = read_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE);
+        let read_map = read_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP);
+        let mode = read_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE);
+        let misc = read_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC);
+
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK, 0x04); // plane 2 only
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE, 0x07); // sequential
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP, 0x02); // read plane 2
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, 0x00); // no odd/even
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC, 0x04); // 0xa0000, 64K
+
+        let result = f(FONT_MEMORY as *mut u8);
+
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MAP_MASK, map_mask);
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, SEQUENCER_MEMORY_MODE, memory_mode);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_READ_MAP, read_map);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, mode);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MISC, misc);
+
+        result
+    })
+}
+
+/// Saves the firmware font the first time it is about to be overwritten
+unsafe fn save_firmware_font(font: *mut u8) {
+    let mut saved = SAVED_FONT.lock();
+    if saved.is_none() {
+        let mut copy = [0; GLYPH_COUNT * FONT_HEIGHT_80X25];
+        for glyph in 0..GLYPH_COUNT {
+            for line in 0..FONT_HEIGHT_80X25 {
+                let offset = glyph * GLYPH_SLOT_SIZE + line;
+                copy[glyph * FONT_HEIGHT_80X25 + line] = unsafe { font.add(offset).read_volatile() };
+            }
+        }
+        *saved = Some(copy);
+    }
+}
+
+// =============================================================================
+// TEXT MODE SWITCHING
+// =============================================================================
+
+/// Switches the display to 80x50 text mode
+///
+/// STUDY NOTE: 80x25 mode shows 400 scan lines using a 16 line font. By
+/// loading an 8 line font and telling the CRTC that each character row is
+/// only 8 scan lines high, the same 400 scan lines show 50 rows instead. The
+/// 8x8 font is derived from the firmware font by merging pairs of scan
+/// lines, so thin horizontal strokes don't disappear.
+#[allow(dead_code)]
+pub fn set_80x50_mode() {
+    unsafe {
+        with_font_memory(|font| {
+            save_firmware_font(font);
+            let saved = SAVED_FONT.lock();
+            let original = saved.as_ref().unwrap();
+            for glyph in 0..GLYPH_COUNT {
+                for line in 0..FONT_HEIGHT_80X50 {
+                    let source = glyph * FONT_HEIGHT_80X25 + line * 2;
+                    let merged = original[source] | original[source + 1];
+                    font.add(glyph * GLYPH_SLOT_SIZE + line).write_volatile(merged);
+                }
+            }
+        });
+        set_character_height(FONT_HEIGHT_80X50 as u8);
+    }
+    WRITER.lock().set_dimensions(80, 50);
+}
+
+/// Switches the display back to the default 80x25 text mode
+///
+/// Restores the firmware font if `set_80x50_mode` replaced it.
+#[allow(dead_code)]
+pub fn set_80x25_mode() {
+    unsafe {
+        with_font_memory(|font| {
+            if let Some(original) = SAVED_FONT.lock().as_ref() {
+                for glyph in 0..GLYPH_COUNT {
+                    for line in 0..FONT_HEIGHT_80X25 {
+                        let offset = glyph * GLYPH_SLOT_SIZE + line;
+                        font.add(offset).write_volatile(original[glyph * FONT_HEIGHT_80X25 + line]);
+                    }
+                }
+            }
+        });
+        set_character_height(FONT_HEIGHT_80X25 as u8);
+    }
+    WRITER.lock().set_dimensions(80, 25);
+}
+
+/// Sets the number of scan lines per character row
+///
+/// The cursor is moved to the last two scan lines of the new height so it
+/// keeps its usual underline shape.
+unsafe fn set_character_height(lines: u8) {
+    unsafe {
+        update_register(CRTC_INDEX, CRTC_DATA, CRTC_MAX_SCAN_LINE, 0x1F, lines - 1);
+        update_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_START, 0x1F, lines - 2);
+        update_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_END, 0x1F, lines - 1);
+    }
+}
+
+// =============================================================================
+// BLINK / BRIGHT BACKGROUND
+// =============================================================================
+
+/// Bit of the attribute mode control register that enables blinking
+const BLINK_ENABLE: u8 = 0x08;
+/// Palette address source bit, must stay set or the screen goes blank
+const PALETTE_ADDRESS_SOURCE: u8 = 0x20;
+
+/// Reads an attribute controller register
+///
+/// STUDY NOTE: The attribute controller is the odd one out: index and data
+/// share port 0x3C0, and an internal flip-flop decides whether the next
+/// write to it is an index or a value. Reading the input status register
+/// (0x3DA) resets the flip-flop to "index". Data is read back from 0x3C1.
+/// The palette address source bit must be included in the index, because
+/// clearing it disconnects the palette and blanks the display.
+unsafe fn read_attribute_register(index: u8) -> u8 {
+    unsafe {
+        Port::<u8>::new(INPUT_STATUS_1).read();
+        Port::<u8>::new(ATTRIBUTE_INDEX_DATA).write(index | PALETTE_ADDRESS_SOURCE);
+        Port::<u8>::new(ATTRIBUTE_DATA_READ).read()
+    }
+}
+
+/// Writes an attribute controller register (see `read_attribute_register`)
+unsafe fn write_attribute_register(index: u8, value: u8) {
+    unsafe {
+        Port::<u8>::new(INPUT_STATUS_1).read();
+        let mut port = Port::<u8>::new(ATTRIBUTE_INDEX_DATA);
+        port.write(index | PALETTE_ADDRESS_SOURCE);
+        port.write(value);
+    }
+}
+
+/// Returns whether attribute bit 7 currently means "blink" (true) or
+/// "bright background" (false)
+///
+/// The kernel never changes this on its own, so until `set_blink_mode` is
+/// called this reports whatever the firmware configured (usually blink).
+#[allow(dead_code)]
+pub fn blink_mode() -> bool {
+    interrupts::without_interrupts(|| unsafe {
+        read_attribute_register(ATTRIBUTE_MODE_CONTROL) & BLINK_ENABLE != 0
+    })
+}
+
+/// Chooses what attribute bit 7 means
+///
+/// With blinking enabled, characters with bit 7 set blink and only the 8
+/// dark colors are available as backgrounds. With it disabled, bit 7 makes
+/// the background bright, so all 16 colors can be used as backgrounds.
+#[allow(dead_code)]
+pub fn set_blink_mode(enabled: bool) {
+    interrupts::without_interrupts(|| unsafe {
+        let mode = read_attribute_register(ATTRIBUTE_MODE_CONTROL);
+        let mode = if enabled { mode | BLINK_ENABLE } else { mode & !BLINK_ENABLE };
+        write_attribute_register(ATTRIBUTE_MODE_CONTROL, mode);
+    })
+}
+
+// =============================================================================
+// HARDWARE CURSOR
+// =============================================================================
+
+/// Reads the hardware cursor position as a linear cell index
+///
+/// STUDY NOTE: The CRTC stores the cursor as an offset from the start of
+/// the text buffer (row * width + col), split over two 8-bit registers.
+pub fn cursor_location() -> u16 {
+    interrupts::without_interrupts(|| unsafe {
+        let high = read_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_HIGH);
+        let low = read_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_LOW);
+        u16::from_be_bytes([high, low])
+    })
+}
+
+/// Cursor start register bit that hides the cursor
+const CURSOR_DISABLE: u8 = 0x20;
+
+/// The look of the hardware cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    /// The last two scan lines of the cell, like most firmware sets it
+    Underline,
+    /// Every scan line of the cell
+    Block,
+    /// No cursor at all
+    Hidden,
+}
+
+/// The cursor shape `vga_buffer::init` sets, chosen with the `cursor-block`
+/// and `cursor-hidden` features
+pub const BOOT_CURSOR_SHAPE: CursorShape = if cfg!(feature = "cursor-hidden") {
+    CursorShape::Hidden
+} else if cfg!(feature = "cursor-block") {
+    CursorShape::Block
+} else {
+    CursorShape::Underline
+};
+
+/// Changes the shape of the hardware cursor, or hides it
+///
+/// STUDY NOTE: The cursor is drawn on the scan lines from the cursor start
+/// to the cursor end register, counted from the top of the character cell.
+/// The shape is computed from the current character height, so call this
+/// again after a mode switch: `set_80x25_mode` and `set_80x50_mode` reset
+/// the scan lines to an underline (but keep a hidden cursor hidden).
+pub fn set_cursor_shape(shape: CursorShape) {
+    interrupts::without_interrupts(|| unsafe {
+        let last_line = read_register(CRTC_INDEX, CRTC_DATA, CRTC_MAX_SCAN_LINE) & 0x1F;
+        let (start, end) = match shape {
+            CursorShape::Block => (0, last_line),
+            _ => (last_line.saturating_sub(1), last_line),
+        };
+        let disable = if shape == CursorShape::Hidden { CURSOR_DISABLE } else { 0 };
+        update_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_START, 0x3F, start | disable);
+        update_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_END, 0x1F, end);
+    })
+}
+
+// =============================================================================
+// CUSTOM GLYPHS
+// =============================================================================
+
+/// Replaces the bitmap of a single character in the VGA font
+///
+/// Each byte of `bitmap` is one scan line from top to bottom, with the most
+/// significant bit being the leftmost pixel. Every cell on screen that shows
+/// `code` changes immediately, since the hardware reads the font on every
+/// refresh.
+///
+/// STUDY NOTE: In 80x50 mode only the first 8 scan lines are displayed. The
+/// glyph is also stored in the saved firmware font (if one was saved), so a
+/// later switch back to 80x25 keeps the custom glyph.
+pub fn set_glyph(code: u8, bitmap: [u8; 16]) {
+    let glyph = code as usize;
+    unsafe {
+        with_font_memory(|font| {
+            for (line, &bits) in bitmap.iter().enumerate() {
+                font.add(glyph * GLYPH_SLOT_SIZE + line).write_volatile(bits);
+            }
+        });
+    }
+    if let Some(saved) = SAVED_FONT.lock().as_mut() {
+        let start = glyph * FONT_HEIGHT_80X25;
+        saved[start..start + FONT_HEIGHT_80X25].copy_from_slice(&bitmap);
+    }
+}
+
+// =============================================================================
+// SELF-TEST
+// =============================================================================
+
+/// Checks that the CRT controller registers can be written and read back
+///
+/// STUDY NOTE: The cursor location register is harmless to change for a
+/// moment, so we write two complementary bit patterns to it and restore the
+/// original value afterwards. If no VGA hardware answers on the ports,
+/// reads return 0xff and the check fails.
+pub fn verify_registers() -> bool {
+    interrupts::without_interrupts(|| unsafe {
+        let original = read_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_LOW);
+        let ok = [0x55, 0xaa].iter().all(|&pattern| {
+            write_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_LOW, pattern);
+            read_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_LOW) == pattern
+        });
+        write_register(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOCATION_LOW, original);
+        ok
+    })
+}
@@ -10,9 +10,14 @@
 // - Each character on screen requires 2 bytes: character byte + attribute byte
 // - The attribute byte contains color information (foreground and background)
 
+use crate::cp437;
+use crate::pit;
+use crate::vga;
 use core::fmt;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
-use spin::Mutex;
+use spin::{Mutex, MutexGuard};
 use volatile::Volatile;
 
 // STUDY NOTE: We use lazy_static! to create a global writer instance.
@@ -22,11 +27,74 @@ lazy_static! {
     /// Global writer instance protected by a spinlock mutex
     /// A spinlock doesn't put the thread to sleep - it keeps checking in a loop
     /// This is important in kernel code where we don't have thread scheduling yet
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        color_code: ColorCode::new(Color::Yellow, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
+    ///
+    /// STUDY NOTE: `spin::Mutex` already calls the spin loop hint (`pause`)
+    /// while it waits for the lock, so waiting for the WRITER is as cheap for
+    /// the CPU as our own busy-wait loops.
+    ///
+    /// STUDY NOTE: 0xb8000 is a physical address. We can use it as a pointer
+    /// only because the bootloader identity-maps the VGA memory window
+    /// 0xa0000..0xc0000 before jumping to `_start` (the `vga` module uses
+    /// the same window for font memory). Paging code that builds its own
+    /// page tables must keep this range mapped, or the first print will
+    /// cause a page fault.
+    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer::new(
+        // The WRITER is the only user of the color text buffer at 0xb8000
+        unsafe { Buffer::at(0xb8000) },
+        DEFAULT_COLOR,
+    ));
+}
+
+/// Resets the global `WRITER` to the default settings and clears the screen
+///
+/// The test runner calls this before every test, so each test starts on a
+/// blank screen at column 0 with the default settings, whatever the test
+/// before it left behind.
+///
+/// STUDY NOTE: Calling `Buffer::at(0xb8000)` again would create a second
+/// `&mut` to the buffer while the old writer still holds the first one.
+/// Instead, the existing reference is moved out of the old writer and into
+/// the new one, which then overwrites the old writer in place.
+#[cfg(test)]
+pub fn reset_writer() {
+    let mut guard = WRITER.lock();
+    let writer: &mut Writer = &mut guard;
+    // SAFETY: The old writer is overwritten without being dropped or used
+    // again, so the buffer reference is moved, not duplicated. `Writer::new`
+    // can't panic between the read and the write, since 80x25 always fits.
+    unsafe {
+        let buffer = core::ptr::read(&writer.buffer);
+        core::ptr::write(writer, Writer::new(buffer, DEFAULT_COLOR));
+    }
+    for row in 0..writer.height {
+        writer.clear_row(row);
+    }
+}
+
+/// Set by `init` once the global `WRITER` exists
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Initializes the global `WRITER`
+///
+/// This should be one of the first steps in `_start`: in debug builds,
+/// `print!` before it fails an assertion. The same goes for any other entry
+/// point, like a test runner: `_start` calls this before `test_main`, so
+/// tests can print. It also sets the boot cursor shape (see
+/// `vga::BOOT_CURSOR_SHAPE`).
+///
+/// STUDY NOTE: lazy_static! would create the WRITER on the first print,
+/// wherever that happens to be. Calling this early in `_start` makes the
+/// initialization happen at a known point instead. It must run before
+/// interrupts (and, one day, other CPU cores) are enabled: then nothing can
+/// race with the one-time initialization. Calling it again does nothing.
+pub fn init() {
+    debug_assert!(
+        !x86_64::instructions::interrupts::are_enabled(),
+        "vga_buffer::init must run before interrupts are enabled"
+    );
+    lazy_static::initialize(&WRITER);
+    vga::set_cursor_shape(vga::BOOT_CURSOR_SHAPE);
+    READY.store(true, Ordering::Release);
 }
 
 // =============================================================================
@@ -58,20 +126,63 @@ pub enum Color {
     White = 15,
 }
 
+impl Color {
+    /// Converts the low four bits of a color byte into a Color
+    fn from_nibble(value: u8) -> Color {
+        match value & 0x0f {
+            0 => Color::Black,
+            1 => Color::Blue,
+            2 => Color::Green,
+            3 => Color::Cyan,
+            4 => Color::Red,
+            5 => Color::Magenta,
+            6 => Color::Brown,
+            7 => Color::LightGray,
+            8 => Color::DarkGray,
+            9 => Color::LightBlue,
+            10 => Color::LightGreen,
+            11 => Color::LightCyan,
+            12 => Color::LightRed,
+            13 => Color::Pink,
+            14 => Color::Yellow,
+            _ => Color::White,
+        }
+    }
+}
+
 /// Represents a full color code (foreground + background)
 /// STUDY NOTE: The color byte format is:
 /// - Bits 0-3: Foreground color
 /// - Bits 4-6: Background color
-/// - Bit 7: Blink bit (we don't use this)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// - Bit 7: Blink bit, or bright background (see `vga::set_blink_mode`)
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]  // Ensures ColorCode has same memory layout as u8
 struct ColorCode(u8);
 
 impl ColorCode {
     /// Creates a new ColorCode from foreground and background colors
-    fn new(foreground: Color, background: Color) -> ColorCode {
+    const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    /// Returns the foreground color (bits 0-3)
+    fn foreground(self) -> Color {
+        Color::from_nibble(self.0)
+    }
+
+    /// Returns the background color (bits 4-7)
+    fn background(self) -> Color {
+        Color::from_nibble(self.0 >> 4)
+    }
+}
+
+// STUDY NOTE: The derived Debug would print `ColorCode(14)`, which forces
+// you to decode the bits by hand. Printing the color names is much more
+// useful when a test fails.
+impl fmt::Debug for ColorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} on {:?}", self.foreground(), self.background())
+    }
 }
 
 // =============================================================================
@@ -81,25 +192,157 @@ impl ColorCode {
 /// Represents a single character on the screen
 /// STUDY NOTE: #[repr(C)] ensures the struct has the same memory layout
 /// as it would in C, which is important for hardware compatibility
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
-struct ScreenChar {
+pub struct ScreenChar {
     ascii_character: u8,
     color_code: ColorCode,
 }
 
-/// The height of the VGA text buffer (number of rows)
+impl ScreenChar {
+    /// Returns the character byte of this cell
+    #[allow(dead_code)]
+    pub fn character(&self) -> u8 {
+        self.ascii_character
+    }
+
+    /// Returns the foreground color of this cell
+    #[allow(dead_code)]
+    pub fn foreground(&self) -> Color {
+        self.color_code.foreground()
+    }
+
+    /// Returns the background color of this cell
+    #[allow(dead_code)]
+    pub fn background(&self) -> Color {
+        self.color_code.background()
+    }
+
+    /// Returns the raw attribute byte of this cell
+    #[allow(dead_code)]
+    pub fn color_byte(&self) -> u8 {
+        self.color_code.0
+    }
+}
+
+/// Formats a cell like `ScreenChar('A', Yellow on Black)`
+/// Bytes outside printable ASCII are shown in hex, e.g. `ScreenChar(0xFE, ...)`
+impl fmt::Debug for ScreenChar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ascii_character {
+            byte @ 0x20..=0x7e => write!(f, "ScreenChar({:?}, {:?})", byte as char, self.color_code),
+            byte => write!(f, "ScreenChar(0x{:02X}, {:?})", byte, self.color_code),
+        }
+    }
+}
+
+// Code page 437 box-drawing characters used by `Writer::draw_box`, or plain
+// ASCII look-alikes with the `ascii-strict` feature
+pub(crate) const BOX_HORIZONTAL: u8 = ascii_or(b'-', 0xc4);
+pub(crate) const BOX_VERTICAL: u8 = ascii_or(b'|', 0xb3);
+pub(crate) const BOX_TOP_LEFT: u8 = ascii_or(b'+', 0xda);
+pub(crate) const BOX_TOP_RIGHT: u8 = ascii_or(b'+', 0xbf);
+pub(crate) const BOX_BOTTOM_LEFT: u8 = ascii_or(b'+', 0xc0);
+pub(crate) const BOX_BOTTOM_RIGHT: u8 = ascii_or(b'+', 0xd9);
+
+/// Picks `ascii` with the `ascii-strict` feature and `cp437` otherwise
+///
+/// STUDY NOTE: A `const fn`, so glyph constants elsewhere (like the memory
+/// map's block) can be defined the same way.
+pub(crate) const fn ascii_or(ascii: u8, cp437: u8) -> u8 {
+    if cfg!(feature = "ascii-strict") {
+        ascii
+    } else {
+        cp437
+    }
+}
+
+/// The colors `Writer::write_rainbow` cycles through
+const RAINBOW_PALETTE: [Color; 6] = [
+    Color::LightRed,
+    Color::Yellow,
+    Color::LightGreen,
+    Color::LightCyan,
+    Color::LightBlue,
+    Color::Pink,
+];
+
+/// The glyph shown for unsupported bytes by default (■ in code page 437)
+const DEFAULT_REPLACEMENT_GLYPH: u8 = 0xfe;
+
+/// The color the global `WRITER` starts with
+const DEFAULT_COLOR: ColorCode = ColorCode::new(Color::Yellow, Color::Black);
+
+/// The color of every cell in monochrome mode (see `set_monochrome`)
+const MONOCHROME_COLOR: ColorCode = ColorCode(0x07);
+
+/// Distance between two tab stops by default
+const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// Digits of the line numbers shown in the gutter (they wrap around after)
+const LINE_NUMBER_DIGITS: usize = 5;
+/// Width of the line number gutter: the number, a space, `|` and a space
+const GUTTER_WIDTH: usize = LINE_NUMBER_DIGITS + 3;
+
+/// Lines a string must span before `write_string` batches its scrolls
+const DEFAULT_SCROLL_BATCH_THRESHOLD: usize = 2;
+
+/// The column width keys are padded to by `Writer::write_kv`
+const KV_KEY_WIDTH: usize = 12;
+
+/// The height of the VGA text buffer in the default 80x25 mode (number of rows)
 const BUFFER_HEIGHT: usize = 25;
-/// The width of the VGA text buffer (number of columns)
+/// The width of the VGA text buffer in the default 80x25 mode (number of columns)
 const BUFFER_WIDTH: usize = 80;
+/// The widest row any supported text mode uses
+const MAX_BUFFER_WIDTH: usize = 80;
+/// The number of cells in the largest supported text mode (80x50)
+const MAX_BUFFER_CELLS: usize = MAX_BUFFER_WIDTH * 50;
 
 /// Represents the VGA text buffer
 /// STUDY NOTE: We use Volatile to prevent compiler optimizations.
 /// Without volatile, the compiler might optimize away repeated writes
 /// thinking they're redundant, but we need every write to reach the hardware.
+///
+/// STUDY NOTE: The buffer is stored as a flat array of cells rather than a
+/// fixed 2D grid. The hardware lays rows out back to back, so cell (row, col)
+/// lives at index `row * width + col` for whatever width the current mode
+/// uses. This lets the same Buffer serve 80x25, 80x50 and 40x25 modes.
 #[repr(transparent)]
 struct Buffer {
-    chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    chars: [Volatile<ScreenChar>; MAX_BUFFER_CELLS],
+}
+
+// STUDY NOTE: The hardware expects exactly two bytes per cell: the character
+// and then the color. These checks make the build fail if a change to the
+// types above ever breaks that layout, instead of showing garbage on screen.
+const _: () = assert!(core::mem::size_of::<ColorCode>() == 1);
+const _: () = assert!(core::mem::size_of::<ScreenChar>() == 2);
+const _: () = assert!(core::mem::align_of::<ScreenChar>() == 1);
+const _: () = assert!(core::mem::size_of::<Buffer>() == MAX_BUFFER_CELLS * 2);
+
+impl Buffer {
+    /// Returns the text buffer at the given address
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to `MAX_BUFFER_CELLS` cells of mapped text buffer
+    /// memory, which must stay mapped forever. Nothing else may access that
+    /// memory through another reference, so this must be called only once
+    /// per address.
+    ///
+    /// Panics if `addr` is null or not a multiple of the 2-byte cell size.
+    unsafe fn at(addr: usize) -> &'static mut Buffer {
+        // STUDY NOTE: `Buffer` only has an alignment of 1 (see the layout
+        // checks above), so checking its alignment would accept any
+        // address. Text buffers always start on a cell boundary instead.
+        assert!(
+            addr != 0 && addr.is_multiple_of(core::mem::size_of::<ScreenChar>()),
+            "invalid VGA buffer address 0x{:x}",
+            addr
+        );
+        unsafe { &mut *(addr as *mut Buffer) }
+    }
 }
 
 // =============================================================================
@@ -108,146 +351,2306 @@ struct Buffer {
 
 /// A writer that can write ASCII bytes and strings to the VGA text buffer
 pub struct Writer {
-    column_position: usize,  // Current column position (0-79)
+    column_position: usize,  // Current column position (0..width)
+    width: usize,            // Number of columns in the current text mode
+    height: usize,           // Number of rows in the current text mode
     color_code: ColorCode,   // Current color configuration
+    attributes: Attributes,  // Text effects applied on top of the color
+    adapter: Adapter,        // Kind of display adapter the attributes map to
+    scroll_enabled: bool,    // Whether a new line at the bottom scrolls
+    scroll_overflow: ScrollOverflow,  // What to do instead while scrolling is off
+    dropping_output: bool,   // Set once output overflowed with ScrollOverflow::Drop
+    replacement_glyph: u8,   // Shown in place of bytes outside printable ASCII
+    scroll_batch_threshold: usize,  // Lines a string needs before scrolls are batched
+    batched_lines: usize,    // Lines of the current string not scrolled in yet
+    pending_lines: usize,    // Blank rows below the cursor from a batched scroll
+    line_numbers: bool,      // Whether lines start with a line number gutter
+    lines_written: usize,    // Number of '\n' written so far
+    wrapped: bool,           // Whether the current line continues a wrapped one
+    tab_width: usize,        // Distance between two tab stops
+    right_margin: usize,     // Column at which lines wrap, if narrower than the screen
+    monochrome: bool,        // Whether every cell is drawn in MONOCHROME_COLOR
+    faded_out: bool,         // Whether fade_colors holds the colors before fade_out
+    fade_colors: [u8; MAX_BUFFER_CELLS],  // Colors fade_in brightens the screen to
     buffer: &'static mut Buffer,  // Reference to the VGA buffer
 }
 
+/// Text effects a writer can apply on top of its color
+///
+/// STUDY NOTE: VGA has no real text styles, so each effect is mapped to
+/// the closest thing the active adapter can display:
+///
+/// | Effect    | Color adapter                   | Monochrome adapter    |
+/// |-----------|---------------------------------|-----------------------|
+/// | bold      | bright foreground color         | high intensity        |
+/// | underline | no effect                       | underlined text       |
+/// | reverse   | foreground and background swap, | black on light        |
+/// |           | with a dark background          |                       |
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attributes {
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Attributes {
+    /// No text effects
+    pub const NONE: Attributes = Attributes {
+        bold: false,
+        underline: false,
+        reverse: false,
+    };
+}
+
+/// The kind of display adapter attribute bytes are interpreted by
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adapter {
+    /// A color adapter (CGA/EGA/VGA): 4 bit foreground and background colors
+    Color,
+    /// A monochrome adapter (MDA): colors are ignored, but text can be
+    /// underlined
+    Monochrome,
+}
+
+/// What happens to output that would scroll while scrolling is disabled
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollOverflow {
+    /// Clear the last line and continue writing there
+    OverwriteLastLine,
+    /// Discard all further output until scrolling is enabled again
+    Drop,
+}
+
+/// The base `Writer::write_u64_padded` prints numbers in
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Decimal,
+    /// Uppercase digits, without a `0x` prefix
+    Hex,
+}
+
 impl Writer {
-    /// Writes a single ASCII byte to the buffer
-    pub fn write_byte(&mut self, byte: u8) {
-        match byte {
-            b'\n' => self.new_line(),  // Handle newline character
-            byte => {
-                // Check if we need to wrap to the next line
-                if self.column_position >= BUFFER_WIDTH {
-                    self.new_line();
-                }
+    /// Creates a writer for an 80x25 text buffer at any address
+    ///
+    /// STUDY NOTE: Taking the buffer as a parameter keeps the hardware
+    /// address out of the writer itself. Tests can hand in a buffer in
+    /// ordinary memory, and after remapping the buffer (or on a monochrome
+    /// adapter at 0xb0000) we only need to pass a different `Buffer::at`.
+    fn new(buffer: &'static mut Buffer, default_color: ColorCode) -> Writer {
+        Writer::new_with_dimensions(BUFFER_WIDTH, BUFFER_HEIGHT, default_color, buffer)
+    }
 
-                let row = BUFFER_HEIGHT - 1;  // Always write to the last row
-                let col = self.column_position;
+    /// Creates a writer for a text mode with the given dimensions
+    ///
+    /// Panics if the dimensions don't fit into the VGA buffer.
+    fn new_with_dimensions(
+        width: usize,
+        height: usize,
+        color_code: ColorCode,
+        buffer: &'static mut Buffer,
+    ) -> Writer {
+        let mut writer = Writer {
+            column_position: 0,
+            width: BUFFER_WIDTH,
+            height: BUFFER_HEIGHT,
+            color_code,
+            attributes: Attributes::NONE,
+            adapter: Adapter::Color,
+            scroll_enabled: true,
+            scroll_overflow: ScrollOverflow::OverwriteLastLine,
+            dropping_output: false,
+            replacement_glyph: DEFAULT_REPLACEMENT_GLYPH,
+            scroll_batch_threshold: DEFAULT_SCROLL_BATCH_THRESHOLD,
+            batched_lines: 0,
+            pending_lines: 0,
+            line_numbers: false,
+            lines_written: 0,
+            wrapped: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            right_margin: BUFFER_WIDTH,
+            monochrome: false,
+            faded_out: false,
+            fade_colors: [0; MAX_BUFFER_CELLS],
+            buffer,
+        };
+        writer.set_dimensions(width, height);
+        writer
+    }
 
-                let color_code = self.color_code;
+    /// Changes the dimensions the writer addresses the buffer with
+    ///
+    /// STUDY NOTE: This only changes how the writer interprets the buffer.
+    /// The VGA hardware must be switched to the matching text mode
+    /// separately (see the `vga` module), otherwise the rows won't line up
+    /// on screen. Rows that become visible are cleared because they contain
+    /// whatever was left in video memory. If the width changes, the old rows
+    /// no longer line up at all, so the whole screen is cleared.
+    pub fn set_dimensions(&mut self, width: usize, height: usize) {
+        assert!(
+            width > 0
+                && width <= MAX_BUFFER_WIDTH
+                && height > 0
+                && width * height <= MAX_BUFFER_CELLS,
+            "text mode {}x{} does not fit into the VGA buffer",
+            width,
+            height
+        );
+        let first_new_row = if width == self.width { self.height } else { 0 };
+        self.right_margin = if self.right_margin == self.width {
+            width
+        } else {
+            self.right_margin.min(width)
+        };
+        self.width = width;
+        self.height = height;
+        self.column_position = self.column_position.min(width);
+        self.tab_width = self.tab_width.min(width);
+        for row in first_new_row..height {
+            self.clear_row(row);
+        }
+    }
 
-                // STUDY NOTE: We write using Volatile to ensure the write
-                // actually happens and isn't optimized away
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code,
-                });
-                self.column_position += 1;
-            }
+    /// Returns the number of columns of the current text mode
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows of the current text mode
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Enables or disables scrolling when output reaches the bottom
+    ///
+    /// STUDY NOTE: With scrolling disabled, the rows above the last one are
+    /// never touched by normal output. This protects a carefully drawn static
+    /// screen from a runaway log. What happens to the overflowing output
+    /// is chosen with `set_scroll_overflow`.
+    #[allow(dead_code)]
+    pub fn set_scroll_enabled(&mut self, on: bool) {
+        self.scroll_enabled = on;
+        self.dropping_output = false;
+    }
+
+    /// Chooses what happens to output that would scroll while scrolling is
+    /// disabled (the default is `ScrollOverflow::OverwriteLastLine`)
+    #[allow(dead_code)]
+    pub fn set_scroll_overflow(&mut self, policy: ScrollOverflow) {
+        self.scroll_overflow = policy;
+    }
+
+    /// Sets how many lines a string must span before its scrolls are batched
+    ///
+    /// 0 turns batching off, so every new line scrolls by one row.
+    #[allow(dead_code)]
+    pub fn set_scroll_batch_threshold(&mut self, lines: usize) {
+        self.scroll_batch_threshold = lines;
+    }
+
+    /// Sets the distance between two tab stops, clamped to `1..=width()`
+    ///
+    /// A later `set_dimensions` to a narrower mode clamps it again.
+    #[allow(dead_code)]
+    pub fn set_tab_width(&mut self, width: usize) {
+        self.tab_width = width.clamp(1, self.width);
+    }
+
+    /// Makes lines wrap at column `col` instead of the right edge of the
+    /// screen, clamped to `1..=width()`
+    ///
+    /// The columns right of the margin are left alone by normal output, e.g.
+    /// for a panel drawn with the positioned write functions. A margin at
+    /// the right edge stays there when `set_dimensions` changes the width;
+    /// any other margin is clamped to the new width. The line number gutter
+    /// can push the margin further right (see `line_width`).
+    #[allow(dead_code)]
+    pub fn set_right_margin(&mut self, col: usize) {
+        self.right_margin = col.clamp(1, self.width);
+    }
+
+    /// Returns the number of columns a line of text can use: the width, or
+    /// the right margin if that is narrower
+    ///
+    /// With the line number gutter on, a margin inside the gutter is moved
+    /// just past it, so every line still has room for one character.
+    pub fn line_width(&self) -> usize {
+        self.right_margin.max(self.gutter_width() + 1).min(self.width)
+    }
+
+    /// The columns the line number gutter takes up at the start of a line
+    ///
+    /// STUDY NOTE: A screen too narrow for the gutter and one character gets
+    /// no gutter at all (0), rather than lines that are all gutter.
+    fn gutter_width(&self) -> usize {
+        if self.line_numbers && self.width > GUTTER_WIDTH {
+            GUTTER_WIDTH
+        } else {
+            0
         }
     }
 
-    /// Writes a string to the buffer
-    pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // Printable ASCII byte or newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // Not part of printable ASCII range
-                // STUDY NOTE: VGA text mode only supports ASCII, not full UTF-8
-                // We display a ■ character for unsupported bytes
-                _ => self.write_byte(0xfe),
-            }
+    /// Enables or disables the line number gutter
+    ///
+    /// While enabled, every line starts with its (1-based) number and a
+    /// separator, like `   42 | message`, which leaves less room for text.
+    /// Wrapped lines get an empty gutter, and empty lines stay empty. The
+    /// number counts every line written, also while the gutter is off.
+    /// Turned on in the middle of a line, it starts with the next line.
+    #[allow(dead_code)]
+    pub fn set_line_numbers(&mut self, on: bool) {
+        self.line_numbers = on;
+    }
+
+    /// Chooses the glyph shown in place of bytes outside printable ASCII
+    ///
+    /// The default is ■ (0xfe). A space hides unsupported bytes entirely,
+    /// which can be less distracting when rendering binary data. With the
+    /// `ascii-strict` feature, `?` is used regardless.
+    #[allow(dead_code)]
+    pub fn set_replacement_glyph(&mut self, byte: u8) {
+        self.replacement_glyph = byte;
+    }
+
+    /// The byte written in place of bytes outside printable ASCII
+    ///
+    /// STUDY NOTE: With the `ascii-strict` feature this is always `?`, so
+    /// `write_string` and `write_char` never put a byte of 0x80 or above on
+    /// screen, even if it is later copied to a terminal that doesn't know
+    /// code page 437. The box and block glyphs switch to ASCII under the
+    /// feature too (see `ascii_or`), but `write_byte` and the positioned
+    /// writes still store whatever byte they are given.
+    /// `cfg!` is a constant, so the other branch is compiled away.
+    fn unsupported_glyph(&self) -> u8 {
+        if cfg!(feature = "ascii-strict") {
+            b'?'
+        } else {
+            self.replacement_glyph
         }
     }
 
-    /// Shifts all lines up by one and clears the last line
-    fn new_line(&mut self) {
-        // STUDY NOTE: When we reach the bottom of the screen, we need to scroll
-        // This is done by copying each row to the row above it
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
+    /// Sets the text effects applied to subsequent writes
+    #[allow(dead_code)]
+    pub fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = attributes;
+    }
+
+    /// Turns reverse video (swapped foreground and background) on or off
+    ///
+    /// The stored color is not changed, so turning it off restores the
+    /// previous look. Useful for highlighting a selected menu item.
+    #[allow(dead_code)]
+    pub fn set_reverse(&mut self, on: bool) {
+        self.attributes.reverse = on;
+    }
+
+    /// Sets the kind of adapter text effects are mapped for
+    #[allow(dead_code)]
+    pub fn set_adapter(&mut self, adapter: Adapter) {
+        self.adapter = adapter;
+    }
+
+    /// Enables or disables monochrome output
+    ///
+    /// While enabled, every cell is drawn light gray on black, whatever
+    /// color was asked for; only the bright bit (e.g. from bold) is kept.
+    /// The color APIs keep working, so the requested colors come back once
+    /// this is turned off again. Cells already on screen turn monochrome
+    /// only when they are redrawn, which includes scrolling.
+    #[allow(dead_code)]
+    pub fn set_monochrome(&mut self, on: bool) {
+        self.monochrome = on;
+    }
+
+    /// Returns the color a cell is actually drawn in
+    fn displayed_color(&self, color_code: ColorCode) -> ColorCode {
+        if self.monochrome {
+            ColorCode(MONOCHROME_COLOR.0 | (color_code.0 & 0x08))
+        } else {
+            color_code
+        }
+    }
+
+    /// Returns the color code with the current text effects applied
+    ///
+    /// STUDY NOTE: The attributes are applied at write time instead of being
+    /// folded into `color_code`, so turning an effect off again restores the
+    /// exact color that was set before.
+    fn effective_color(&self) -> ColorCode {
+        let attributes = self.attributes;
+        match self.adapter {
+            Adapter::Color => {
+                let mut code = self.color_code.0;
+                if attributes.bold {
+                    code |= 0x08;
+                }
+                // STUDY NOTE: Bit 7 is the blink bit under the firmware
+                // default (see `vga::set_blink_mode`), so a bright foreground
+                // can't simply become the background: that would make the
+                // text blink. The new background gets the dark version.
+                if attributes.reverse {
+                    code = (code & 0x07) << 4 | code >> 4;
+                }
+                ColorCode(code)
+            }
+            // STUDY NOTE: MDA attribute bytes: 0x07 is normal text, 0x01
+            // underlined, 0x70 reverse video, and bit 3 selects high intensity.
+            // Reverse video can't be combined with underline.
+            Adapter::Monochrome => {
+                let mut code = match (attributes.reverse, attributes.underline) {
+                    (true, _) => 0x70,
+                    (false, true) => 0x01,
+                    (false, false) => 0x07,
+                };
+                if attributes.bold {
+                    code |= 0x08;
+                }
+                ColorCode(code)
             }
         }
-        self.clear_row(BUFFER_HEIGHT - 1);
-        self.column_position = 0;
     }
 
-    /// Clears a row by filling it with blank characters
-    fn clear_row(&mut self, row: usize) {
+    /// Sets the color used for subsequent writes
+    pub fn set_color(&mut self, foreground: Color, background: Color) {
+        self.color_code = ColorCode::new(foreground, background);
+    }
+
+    /// Returns the current color as a raw attribute byte
+    #[allow(dead_code)]
+    pub fn color_byte(&self) -> u8 {
+        self.color_code.0
+    }
+
+    /// Sets the current color from a raw attribute byte
+    ///
+    /// Unlike `set_color`, this can set bit 7 (blink or bright background)
+    /// directly, and it can copy a color verbatim from another cell.
+    #[allow(dead_code)]
+    pub fn set_color_byte(&mut self, byte: u8) {
+        self.color_code = ColorCode(byte);
+    }
+
+    /// Runs `f` with a temporary color and restores the previous color
+    pub fn with_color<R>(
+        &mut self,
+        foreground: Color,
+        background: Color,
+        f: impl FnOnce(&mut Writer) -> R,
+    ) -> R {
+        let original = self.color_code;
+        self.set_color(foreground, background);
+        let result = f(self);
+        self.color_code = original;
+        result
+    }
+
+    /// Fills a rectangle with blank characters on the given background color
+    ///
+    /// The rectangle is clipped to the screen, so it is fine to pass a size
+    /// that runs past the right or bottom edge.
+    pub fn fill_region(&mut self, row: usize, col: usize, width: usize, height: usize, background: Color) {
         let blank = ScreenChar {
             ascii_character: b' ',
-            color_code: self.color_code,
+            color_code: ColorCode::new(self.color_code.foreground(), background),
         };
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(blank);
+        let bottom = row.saturating_add(height).min(self.height);
+        let right = col.saturating_add(width).min(self.width);
+        for row in row..bottom {
+            for col in col..right {
+                self.write_cell(row, col, blank);
+            }
         }
     }
-}
 
-// =============================================================================
-// FORMATTING SUPPORT
-// =============================================================================
+    /// Draws a single-line box border around a rectangle in the current color
+    ///
+    /// STUDY NOTE: The box-drawing glyphs are part of code page 437, the
+    /// character set built into the VGA font. Like `fill_region`, the box is
+    /// clipped to the screen.
+    pub fn draw_box(&mut self, row: usize, col: usize, width: usize, height: usize) {
+        if width < 2 || height < 2 {
+            return;
+        }
+        // Saturating, so huge sizes put the far edges off screen instead of
+        // overflowing; the loops stop at the screen edge either way
+        let bottom = row.saturating_add(height - 1);
+        let right = col.saturating_add(width - 1);
+        for c in col.saturating_add(1)..right.min(self.width) {
+            self.put_clipped(row, c, BOX_HORIZONTAL);
+            self.put_clipped(bottom, c, BOX_HORIZONTAL);
+        }
+        for r in row.saturating_add(1)..bottom.min(self.height) {
+            self.put_clipped(r, col, BOX_VERTICAL);
+            self.put_clipped(r, right, BOX_VERTICAL);
+        }
+        self.put_clipped(row, col, BOX_TOP_LEFT);
+        self.put_clipped(row, right, BOX_TOP_RIGHT);
+        self.put_clipped(bottom, col, BOX_BOTTOM_LEFT);
+        self.put_clipped(bottom, right, BOX_BOTTOM_RIGHT);
+    }
 
-// STUDY NOTE: Implementing fmt::Write allows us to use write! macro
-// This is how we enable formatted output like numbers, hex values, etc.
-impl fmt::Write for Writer {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.write_string(s);
-        Ok(())
+    /// Writes a string at a fixed position in the current color
+    ///
+    /// Unlike `write_string`, this never wraps or scrolls and doesn't move
+    /// the cursor: text past the end of the row is cut off. Use
+    /// `write_string_at_wrapped` to wrap text inside a box instead.
+    pub fn write_string_at(&mut self, row: usize, col: usize, s: &str) {
+        for (i, byte) in s.bytes().enumerate() {
+            let byte = match byte {
+                0x20..=0x7e => byte,
+                _ => self.unsupported_glyph(),
+            };
+            self.put_clipped(row, col.saturating_add(i), byte);
+        }
     }
-}
 
-// =============================================================================
-// PRINT MACROS
-// =============================================================================
+    /// Writes a string wrapped inside a box of `width` x `height` cells
+    ///
+    /// Lines wrap at the right edge of the box and `\n` starts a new line.
+    /// Like `write_string_at`, this never scrolls: text that doesn't fit
+    /// into the box (or onto the screen) is cut off.
+    #[allow(dead_code)]
+    pub fn write_string_at_wrapped(
+        &mut self,
+        row: usize,
+        col: usize,
+        width: usize,
+        height: usize,
+        s: &str,
+    ) {
+        if width == 0 {
+            return;
+        }
+        let (mut line, mut column) = (0, 0);
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                line += 1;
+                column = 0;
+                continue;
+            }
+            if column == width {
+                line += 1;
+                column = 0;
+            }
+            if line >= height {
+                return;
+            }
+            let byte = match byte {
+                0x20..=0x7e => byte,
+                _ => self.unsupported_glyph(),
+            };
+            self.put_clipped(row.saturating_add(line), col.saturating_add(column), byte);
+            column += 1;
+        }
+    }
 
-/// Like the standard `print!` macro, but prints to the VGA text buffer
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
-}
+    /// Writes a byte in the current color if the position is on screen
+    fn put_clipped(&mut self, row: usize, col: usize, byte: u8) {
+        if row < self.height && col < self.width {
+            let color_code = self.effective_color();
+            self.write_cell(row, col, ScreenChar {
+                ascii_character: byte,
+                color_code,
+            });
+        }
+    }
 
-/// Like the standard `println!` macro, but prints to the VGA text buffer
-#[macro_export]
-macro_rules! println {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
-}
+    /// Inserts a byte at the given position in the current color
+    ///
+    /// The cells from `col` to the end of the row move one position to the
+    /// right first; the last cell of the row falls off. Positions off screen
+    /// are ignored.
+    #[allow(dead_code)]
+    pub fn insert_char_at(&mut self, row: usize, col: usize, byte: u8) {
+        if row >= self.height || col >= self.width {
+            return;
+        }
+        for c in (col + 1..self.width).rev() {
+            let moved = self.read_char_at(row, c - 1);
+            self.write_cell(row, c, moved);
+        }
+        self.put_clipped(row, col, byte);
+    }
 
-/// Prints the given formatted string to the VGA text buffer
-/// through the global `WRITER` instance.
-#[doc(hidden)]
-pub fn _print(args: fmt::Arguments) {
-    use core::fmt::Write;
-    // STUDY NOTE: We use interrupt::without_interrupts to prevent deadlocks
-    // If an interrupt occurs while holding the lock and tries to print,
-    // it would deadlock. We'll add this protection later when we handle interrupts.
-    WRITER.lock().write_fmt(args).unwrap();
-}
+    /// Reads the character at the given position
+    pub fn read_char_at(&self, row: usize, col: usize) -> ScreenChar {
+        self.buffer.chars[self.index(row, col)].read()
+    }
 
-// =============================================================================
-// TESTING
-// =============================================================================
+    /// Writes a character to the given position
+    fn write_cell(&mut self, row: usize, col: usize, character: ScreenChar) {
+        let index = self.index(row, col);
+        let color_code = self.displayed_color(character.color_code);
+        self.buffer.chars[index].write(ScreenChar { color_code, ..character });
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Reads the hardware cursor position and returns it as (row, col)
+    ///
+    /// A position outside the current text mode (e.g. left behind by the
+    /// firmware in a different mode) is clamped to the last cell.
+    #[allow(dead_code)]
+    pub fn read_hardware_cursor(&self) -> (usize, usize) {
+        let last = self.width * self.height - 1;
+        let position = usize::from(vga::cursor_location()).min(last);
+        (position / self.width, position % self.width)
+    }
 
-    #[test_case]
-    fn test_println_simple() {
-        println!("test_println_simple output");
+    /// Computes a hash over every visible cell (characters and colors)
+    ///
+    /// STUDY NOTE: This lets a test compare the whole screen against a known
+    /// snapshot with a single assertion. We use the 32-bit FNV-1a hash, which
+    /// is trivial to implement and good enough to detect any changed cell.
+    /// The cells are read with volatile reads like every other access.
+    #[allow(dead_code)]
+    pub fn checksum(&self) -> u32 {
+        let mut hash: u32 = 0x811c_9dc5;
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let cell = self.read_char_at(row, col);
+                for byte in [cell.ascii_character, cell.color_code.0] {
+                    hash ^= u32::from(byte);
+                    hash = hash.wrapping_mul(0x0100_0193);
+                }
+            }
+        }
+        hash
     }
 
-    #[test_case]
-    fn test_println_many() {
-        for _ in 0..200 {
-            println!("test_println_many output");
+    /// Checks that writes to the VGA buffer can be read back
+    ///
+    /// The top-left cell is overwritten with a few test patterns and then
+    /// restored, so the screen content is unchanged afterwards.
+    pub fn verify_buffer(&mut self) -> bool {
+        let original = self.read_char_at(0, 0);
+        let ok = [0x55, 0xaa].iter().all(|&pattern| {
+            let test_char = ScreenChar {
+                ascii_character: pattern,
+                color_code: ColorCode(pattern),
+            };
+            // Written directly, so monochrome mode can't change the pattern
+            self.buffer.chars[0].write(test_char);
+            self.read_char_at(0, 0) == test_char
+        });
+        self.buffer.chars[0].write(original);
+        ok
+    }
+
+    /// Converts a (row, col) position into an index into the flat buffer
+    fn index(&self, row: usize, col: usize) -> usize {
+        assert!(
+            row < self.height && col < self.width,
+            "position ({}, {}) is outside the {}x{} text buffer",
+            row,
+            col,
+            self.width,
+            self.height
+        );
+        row * self.width + col
+    }
+
+    /// Writes a single ASCII byte to the buffer
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.dropping_output {
+            return;
+        }
+        match byte {
+            b'\n' => {
+                // Handle newline character
+                self.lines_written += 1;
+                self.wrapped = false;
+                self.new_line();
+            }
+            b'\t' => {
+                // STUDY NOTE: A tab moves to the next column that is a
+                // multiple of the tab width. We write spaces on the way, so
+                // old text in between is overwritten like with any other
+                // character.
+                for _ in 0..self.tab_spaces(self.column_position) {
+                    self.write_byte(b' ');
+                }
+            }
+            byte => self.write_glyph(byte),
         }
     }
 
-    #[test_case]
-    fn test_println_output() {
-        let s = "Some test string that fits on a single line";
-        println!("{}", s);
-        for (i, c) in s.chars().enumerate() {
-            let screen_char = WRITER.lock().buffer.chars[BUFFER_HEIGHT - 2][i].read();
-            assert_eq!(char::from(screen_char.ascii_character), c);
+    /// Writes a byte as a glyph, even if it is a control character
+    fn write_glyph(&mut self, byte: u8) {
+        if self.dropping_output {
+            return;
+        }
+        // Check if we need to wrap to the next line
+        if self.column_position >= self.line_width() {
+            self.new_line();
+            self.wrapped = true;
+            if self.dropping_output {
+                return;
+            }
+        }
+        if self.column_position == 0 && self.gutter_width() > 0 {
+            self.write_gutter();
+        }
+
+        let row = self.cursor_row();
+        let col = self.column_position;
+
+        let color_code = self.effective_color();
+
+        // STUDY NOTE: We write using Volatile to ensure the write
+        // actually happens and isn't optimized away
+        self.write_cell(row, col, ScreenChar {
+            ascii_character: byte,
+            color_code,
+        });
+        self.column_position += 1;
+    }
+
+    /// Writes a character, using its code page 437 glyph if it has one
+    ///
+    /// Newlines and tabs are handled like in `write_string`. Characters
+    /// without a glyph are shown as the replacement glyph, and so is every
+    /// non-ASCII character with the `ascii-strict` feature, and U+FFFD (the
+    /// Unicode replacement character). Combining marks are dropped, since
+    /// text mode can't compose them with the glyph before.
+    ///
+    /// STUDY NOTE: Unlike `write_string`, which looks at single bytes, this
+    /// sees whole characters, so 'é' becomes the one glyph 0x82 instead of
+    /// two replacement glyphs for its two UTF-8 bytes.
+    #[allow(dead_code)]
+    pub fn write_char(&mut self, c: char) {
+        match c {
+            '\n' | '\t' => self.write_byte(c as u8),
+            _ if cp437::is_combining_mark(c) => {}
+            char::REPLACEMENT_CHARACTER => self.write_glyph(self.unsupported_glyph()),
+            _ if cfg!(feature = "ascii-strict") && !c.is_ascii() => {
+                self.write_glyph(self.unsupported_glyph())
+            }
+            _ => match cp437::from_char(c) {
+                Some(code) => self.write_glyph(code),
+                None => self.write_glyph(self.unsupported_glyph()),
+            },
+        }
+    }
+
+    /// Writes a string to the buffer
+    pub fn write_string(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Writes bytes that aren't necessarily UTF-8 (like a C string)
+    ///
+    /// Bytes outside printable ASCII are shown as the replacement glyph, just
+    /// like in `write_string`.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        if self.scroll_enabled && self.scroll_batch_threshold > 0 {
+            let lines = self.lines_needed(bytes);
+            if lines >= self.scroll_batch_threshold {
+                self.batched_lines = lines;
+            }
+        }
+        let mut i = 0;
+        while i < bytes.len() {
+            // STUDY NOTE: Fast path - a run of printable bytes that fits on
+            // the current line is copied in one tight loop, instead of going
+            // through `write_byte` (and its checks) for every single byte.
+            // Everything else (newlines, wrapping, unsupported bytes) takes
+            // the regular path below, so the output is exactly the same.
+            // (At the start of a line, the gutter has to be written first.)
+            let space = if self.column_position == 0 && self.gutter_width() > 0 {
+                0
+            } else {
+                self.line_width().saturating_sub(self.column_position)
+            };
+            let run = bytes[i..]
+                .iter()
+                .take(space)
+                .take_while(|byte| matches!(byte, 0x20..=0x7e))
+                .count();
+            if run > 0 && !self.dropping_output {
+                self.write_run(&bytes[i..i + run]);
+                i += run;
+                continue;
+            }
+
+            match bytes[i] {
+                // Printable ASCII byte, newline or tab
+                byte @ (0x20..=0x7e | b'\n' | b'\t') => self.write_byte(byte),
+                // Not part of printable ASCII range
+                // STUDY NOTE: VGA text mode only supports ASCII, not full UTF-8
+                // We display the replacement glyph (■ by default) instead,
+                // through write_glyph so that a glyph like b'\n' is drawn too
+                _ => self.write_glyph(self.unsupported_glyph()),
+            }
+            i += 1;
+        }
+    }
+
+    /// Counts the new lines writing `bytes` will start, including wrapping
+    fn lines_needed(&self, bytes: &[u8]) -> usize {
+        let mut lines = 0;
+        let mut column = self.column_position;
+        for &byte in bytes {
+            if byte == b'\n' {
+                lines += 1;
+                column = 0;
+                continue;
+            }
+            let cells = if byte == b'\t' { self.tab_spaces(column) } else { 1 };
+            for _ in 0..cells {
+                if column >= self.line_width() {
+                    lines += 1;
+                    column = 0;
+                }
+                if column == 0 {
+                    column = self.gutter_width();
+                }
+                column += 1;
+            }
+        }
+        lines
+    }
+
+    /// The number of spaces a tab written at `column` expands to
+    ///
+    /// Tab stops are counted from the end of the line number gutter, so
+    /// tabbed text lines up the same with and without it.
+    fn tab_spaces(&self, column: usize) -> usize {
+        // At the right edge, the tab starts over on the next line, and at
+        // the start of a line the gutter is written before it
+        let column = if column >= self.line_width() { 0 } else { column };
+        let gutter = self.gutter_width();
+        self.tab_width - (column.max(gutter) - gutter) % self.tab_width
+    }
+
+    /// Writes the line number gutter at the start of the cursor row
+    fn write_gutter(&mut self) {
+        let mut gutter = [b' '; GUTTER_WIDTH];
+        gutter[GUTTER_WIDTH - 2] = b'|';
+        if !self.wrapped {
+            let mut number = (self.lines_written + 1) % 10usize.pow(LINE_NUMBER_DIGITS as u32);
+            for digit in gutter[..LINE_NUMBER_DIGITS].iter_mut().rev() {
+                *digit = b'0' + (number % 10) as u8;
+                number /= 10;
+                if number == 0 {
+                    break;
+                }
+            }
+        }
+        let color_code = ColorCode::new(Color::DarkGray, self.color_code.background());
+        let row = self.cursor_row();
+        for (col, &byte) in gutter.iter().enumerate() {
+            self.write_cell(row, col, ScreenChar {
+                ascii_character: byte,
+                color_code,
+            });
+        }
+        self.column_position = GUTTER_WIDTH;
+    }
+
+    /// The row the cursor is on
+    ///
+    /// This is the last row, unless a batched scroll has already made room
+    /// for lines still to come.
+    fn cursor_row(&self) -> usize {
+        self.height - 1 - self.pending_lines
+    }
+
+    /// Writes printable bytes that fit on the current line
+    fn write_run(&mut self, bytes: &[u8]) {
+        let color_code = self.displayed_color(self.effective_color());
+        let start = self.index(self.cursor_row(), self.column_position);
+        let cells = &mut self.buffer.chars[start..start + bytes.len()];
+        for (cell, &byte) in cells.iter_mut().zip(bytes) {
+            cell.write(ScreenChar {
+                ascii_character: byte,
+                color_code,
+            });
+        }
+        self.column_position += bytes.len();
+    }
+
+    /// Fades the text on screen out to black in a few steps
+    ///
+    /// Every foreground color is dimmed through the gray shades
+    /// (White -> LightGray -> DarkGray -> Black); backgrounds stay as they
+    /// are. The writer waits `step_ms` milliseconds between two steps (see
+    /// `pit::delay_us` for how exact that is) and stays locked meanwhile.
+    #[allow(dead_code)]
+    pub fn fade_out(&mut self, step_ms: u64) {
+        self.save_fade_colors();
+        self.faded_out = true;
+        for step in 1..FADE_SHADES.len() {
+            pit::delay_ms(step_ms);
+            for index in 0..self.width * self.height {
+                let cell = self.buffer.chars[index].read();
+                let foreground = fade_shade(cell.foreground(), step);
+                self.set_cell_foreground(index, cell, foreground);
+            }
+        }
+    }
+
+    /// Fades the text on screen in from black, the reverse of `fade_out`
+    ///
+    /// After `fade_out`, the screen gets back the colors it had before the
+    /// fade out (text written in between takes them over too). Otherwise
+    /// the current colors are the target: they are dimmed to black first.
+    /// Either way the last step restores the colors exactly. Like
+    /// `fade_out`, it waits `step_ms` milliseconds between two steps.
+    #[allow(dead_code)]
+    pub fn fade_in(&mut self, step_ms: u64) {
+        if !self.faded_out {
+            self.save_fade_colors();
+        }
+        self.faded_out = false;
+        let cells = self.width * self.height;
+        for step in (0..FADE_SHADES.len()).rev() {
+            for index in 0..cells {
+                let color = self.fade_colors[index];
+                let color_code = ColorCode(color);
+                let cell = ScreenChar { color_code, ..self.buffer.chars[index].read() };
+                let foreground = match step {
+                    0 => color_code.foreground(),
+                    _ => fade_shade(color_code.foreground(), step),
+                };
+                self.set_cell_foreground(index, cell, foreground);
+            }
+            if step > 0 {
+                pit::delay_ms(step_ms);
+            }
+        }
+    }
+
+    /// Remembers the color of every cell for `fade_in`
+    ///
+    /// STUDY NOTE: The screen only shows dimmed colors while fading, so the
+    /// real ones must be kept somewhere else. Without a heap they live in
+    /// the writer itself, which makes it 4000 bytes bigger.
+    fn save_fade_colors(&mut self) {
+        for index in 0..self.width * self.height {
+            self.fade_colors[index] = self.buffer.chars[index].read().color_code.0;
+        }
+    }
+
+    /// Writes a cell with its foreground color replaced, keeping bit 7
+    fn set_cell_foreground(&mut self, index: usize, cell: ScreenChar, foreground: Color) {
+        let color_code = ColorCode((cell.color_code.0 & 0xf0) | foreground as u8);
+        self.buffer.chars[index].write(ScreenChar {
+            color_code: self.displayed_color(color_code),
+            ..cell
+        });
+    }
+
+    /// Writes a string with each character in the next color of the default
+    /// rainbow palette
+    pub fn write_rainbow(&mut self, s: &str) {
+        self.write_rainbow_with(s, &RAINBOW_PALETTE);
+    }
+
+    /// Writes a string cycling the foreground through `palette` per character
+    ///
+    /// The background stays the same, and the writer's color is restored
+    /// afterwards. Newlines don't use up a palette entry.
+    pub fn write_rainbow_with(&mut self, s: &str, palette: &[Color]) {
+        let original = self.color_code;
+        let background = original.background();
+        let mut colors = palette.iter().cycle();
+        let mut bytes = [0; 4];
+        for c in s.chars() {
+            if c != '\n'
+                && let Some(&foreground) = colors.next()
+            {
+                self.color_code = ColorCode::new(foreground, background);
+            }
+            self.write_string(c.encode_utf8(&mut bytes));
+        }
+        self.color_code = original;
+    }
+
+    /// Writes a `key : value` line with the key padded to `KV_KEY_WIDTH`
+    ///
+    /// Keys longer than the pad width are truncated so the values of
+    /// consecutive lines always start in the same column.
+    pub fn write_kv(&mut self, key: &str, value: fmt::Arguments) {
+        use core::fmt::Write;
+        self.write_padded(key, KV_KEY_WIDTH);
+        self.write_string(" : ");
+        self.write_fmt(value).unwrap();
+        self.write_byte(b'\n');
+    }
+
+    /// Writes a list of items in `columns` aligned columns
+    ///
+    /// Items flow left to right and then onto the next row, like words in a
+    /// newspaper column. Every column is as wide as the longest item plus one
+    /// space, but no wider than its share of the screen; longer items are cut
+    /// off. The columns are lined up from the left edge, so a partly written
+    /// line is ended first.
+    #[allow(dead_code)]
+    pub fn write_columns(&mut self, items: &[&str], columns: usize) {
+        if self.column_position != 0 {
+            self.write_byte(b'\n');
+        }
+        let columns = columns.max(1);
+        let longest = items.iter().map(|item| item.len()).max().unwrap_or(0);
+        let field = (longest + 1).min(self.line_width() / columns).max(1);
+        for (i, item) in items.iter().enumerate() {
+            self.write_padded(item, field - 1);
+            let end_of_row = i % columns == columns - 1 || i == items.len() - 1;
+            self.write_byte(if end_of_row { b'\n' } else { b' ' });
+        }
+    }
+
+    /// Writes bytes as space-separated hex pairs, like `00 AB FF`
+    ///
+    /// Meant for a quick look at a short slice within other output. A pair
+    /// is never split at the right edge of the screen; it moves to the next
+    /// row instead.
+    #[allow(dead_code)]
+    pub fn write_bytes_inline(&mut self, bytes: &[u8]) {
+        const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        for (i, &byte) in bytes.iter().enumerate() {
+            if i > 0 && self.column_position < self.line_width() {
+                self.write_byte(b' ');
+            }
+            if self.column_position + 2 > self.line_width() {
+                self.new_line();
+                self.wrapped = true;
+            }
+            self.write_byte(HEX_DIGITS[usize::from(byte >> 4)]);
+            self.write_byte(HEX_DIGITS[usize::from(byte & 0x0f)]);
+        }
+    }
+
+    /// Writes `s` cut off or padded with spaces to exactly `width` cells
+    fn write_padded(&mut self, s: &str, width: usize) {
+        // STUDY NOTE: Every byte occupies exactly one cell (unsupported
+        // bytes become the replacement glyph), so padding by byte length
+        // keeps columns aligned.
+        // We only have to take care not to cut a UTF-8 character in half.
+        let mut end = s.len().min(width);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.write_string(&s[..end]);
+        for _ in end..width {
+            self.write_byte(b' ');
+        }
+    }
+
+    /// Writes a number right-aligned in a field of `width` cells, padded
+    /// with spaces
+    ///
+    /// A number with more digits than `width` is written in full, so the
+    /// column is pushed out of line rather than showing a wrong value.
+    #[allow(dead_code)]
+    pub fn write_u64_padded(&mut self, value: u64, width: usize, radix: Radix) {
+        self.write_u64_filled(value, width, radix, b' ');
+    }
+
+    /// Like `write_u64_padded`, but pads with zeros, like `0000BEEF`
+    #[allow(dead_code)]
+    pub fn write_u64_zero_padded(&mut self, value: u64, width: usize, radix: Radix) {
+        self.write_u64_filled(value, width, radix, b'0');
+    }
+
+    /// Writes `value` in `radix`, with `fill` in front up to `width` cells
+    fn write_u64_filled(&mut self, value: u64, width: usize, radix: Radix, fill: u8) {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        let base = match radix {
+            Radix::Decimal => 10,
+            Radix::Hex => 16,
+        };
+        // STUDY NOTE: u64::MAX has 20 decimal digits. The digits come out
+        // last one first, so we fill the buffer from the end.
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut rest = value;
+        loop {
+            start -= 1;
+            digits[start] = DIGITS[(rest % base) as usize];
+            rest /= base;
+            if rest == 0 {
+                break;
+            }
+        }
+        for _ in digits.len() - start..width {
+            self.write_byte(fill);
+        }
+        for &digit in &digits[start..] {
+            self.write_byte(digit);
+        }
+    }
+
+    /// Shifts all lines up by one and clears the last line
+    fn new_line(&mut self) {
+        if !self.scroll_enabled {
+            match self.scroll_overflow {
+                ScrollOverflow::OverwriteLastLine => {
+                    self.clear_row(self.height - 1);
+                    self.column_position = 0;
+                }
+                ScrollOverflow::Drop => self.dropping_output = true,
+            }
+            return;
+        }
+        // STUDY NOTE: When we reach the bottom of the screen, we need to scroll
+        // This is done by copying each row to the row above it
+        //
+        // Every scroll rewrites the whole screen, no matter by how many rows.
+        // So when `write_string` knows that more lines are coming, we scroll
+        // them all in at once and move the cursor down through the blank
+        // rows afterwards. The screen ends up exactly the same.
+        if self.pending_lines > 0 {
+            self.pending_lines -= 1;
+        } else {
+            let lines = self.batched_lines.min(self.height - 1).max(1);
+            self.scroll(lines);
+            self.pending_lines = lines - 1;
+            self.batched_lines = self.batched_lines.saturating_sub(lines);
+        }
+        self.column_position = 0;
+    }
+
+    /// Shifts the screen content up by `lines` rows and clears the bottom rows
+    ///
+    /// This is cheaper than calling `new_line` repeatedly since every cell is
+    /// copied at most once. The column position is left unchanged. Scrolling
+    /// by the screen height or more clears the whole screen.
+    pub fn scroll(&mut self, lines: usize) {
+        self.shift_rows_up(0, self.height - 1, lines);
+    }
+
+    /// Shifts rows `top..=bottom` up by `lines` rows within that region
+    ///
+    /// Rows shifted past `top` are lost and the rows freed at the bottom of
+    /// the region are cleared; rows outside the region are not touched.
+    /// Shifting by the region height or more clears the whole region.
+    ///
+    /// STUDY NOTE: Every kind of scrolling goes through this one function, so
+    /// the row arithmetic only has to be right in one place.
+    fn shift_rows_up(&mut self, top: usize, bottom: usize, lines: usize) {
+        debug_assert!(
+            top <= bottom && bottom < self.height,
+            "rows {}..={} are not on screen",
+            top,
+            bottom
+        );
+        let lines = lines.min(bottom - top + 1);
+        for row in top + lines..=bottom {
+            for col in 0..self.width {
+                let character = self.read_char_at(row, col);
+                self.write_cell(row - lines, col, character);
+            }
+        }
+        for row in bottom + 1 - lines..=bottom {
+            self.clear_row(row);
+        }
+    }
+
+    /// Clears a row by filling it with blank characters
+    fn clear_row(&mut self, row: usize) {
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+        for col in 0..self.width {
+            self.write_cell(row, col, blank);
+        }
+    }
+}
+
+// =============================================================================
+// FORMATTING SUPPORT
+// =============================================================================
+
+// STUDY NOTE: Implementing fmt::Write allows us to use write! macro
+// This is how we enable formatted output like numbers, hex values, etc.
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// PRINT MACROS
+// =============================================================================
+
+/// Like the standard `print!` macro, but prints to the VGA text buffer
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
+}
+
+/// Like the standard `println!` macro, but prints to the VGA text buffer
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Prints `key : value` lines with aligned values to the VGA text buffer
+///
+/// Pairs are separated by `;`, and each value takes format arguments:
+/// `kv_table!("Answer" => "{}", 42; "Address" => "0x{:X}", 0xb8000);`
+#[macro_export]
+macro_rules! kv_table {
+    ($($key:expr => $($value:expr),+);+ $(;)?) => {
+        $($crate::vga_buffer::_print_kv($key, format_args!($($value),+));)+
+    };
+}
+
+/// Prints one `key : value` line through the global `WRITER` instance.
+#[doc(hidden)]
+#[allow(dead_code)]
+pub fn _print_kv(key: &str, value: fmt::Arguments) {
+    if !OUTPUT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    WRITER.lock().write_kv(key, value);
+}
+
+/// Whether the print macros write to the screen (see `set_output_enabled`)
+static OUTPUT_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables the output of `print!`, `println!`, `try_print!`,
+/// `try_println!`, `smart_println!`, `kv_table!`, `type_out` and `cprintf`
+///
+/// STUDY NOTE: Printing to the screen takes time and a lock, which can hide
+/// timing bugs while debugging interrupts. Disabled output is a cheap no-op:
+/// the format arguments are not even evaluated into text. The panic screen
+/// does not go through the print macros, so crashes are always shown.
+#[allow(dead_code)]
+pub fn set_output_enabled(enabled: bool) {
+    OUTPUT_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether output is enabled (see `set_output_enabled`)
+pub fn output_enabled() -> bool {
+    OUTPUT_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Prints the given formatted string to the VGA text buffer
+/// through the global `WRITER` instance.
+///
+/// In debug builds this panics if `init` hasn't run yet, so every entry
+/// point (including the test runner's) must call `init` before printing.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    // STUDY NOTE: debug_assert! compiles to nothing in release builds, so
+    // this check costs nothing there.
+    debug_assert!(READY.load(Ordering::Acquire), "print! before vga_buffer::init");
+    if !OUTPUT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    // STUDY NOTE: We use interrupt::without_interrupts to prevent deadlocks
+    // If an interrupt occurs while holding the lock and tries to print,
+    // it would deadlock. We'll add this protection later when we handle interrupts.
+    WRITER.lock().write_fmt(args).unwrap();
+}
+
+/// Like `print!`, but fails instead of waiting for the WRITER lock
+///
+/// Evaluates to `Result<(), TryPrintError>`.
+#[macro_export]
+macro_rules! try_print {
+    ($($arg:tt)*) => ($crate::vga_buffer::_try_print(format_args!($($arg)*)));
+}
+
+/// Like `println!`, but fails instead of waiting for the WRITER lock
+///
+/// Evaluates to `Result<(), TryPrintError>`.
+#[macro_export]
+macro_rules! try_println {
+    () => ($crate::try_print!("\n"));
+    ($($arg:tt)*) => ($crate::try_print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Why `try_print!` or `try_println!` printed nothing (or only part)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum TryPrintError {
+    /// Someone else holds the WRITER lock
+    LockBusy,
+    /// A `Display` or `Debug` implementation returned an error
+    Format,
+}
+
+/// Prints the given formatted string if the WRITER lock is free
+///
+/// STUDY NOTE: If an interrupt handler calls `println!` while the code it
+/// interrupted holds the WRITER lock, the handler spins forever: the lock
+/// can only be released once the handler returns. `try_lock` doesn't wait,
+/// so the handler can give up on its output instead.
+#[doc(hidden)]
+#[allow(dead_code)]
+pub fn _try_print(args: fmt::Arguments) -> Result<(), TryPrintError> {
+    use core::fmt::Write;
+    if !OUTPUT_ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mut writer = WRITER.try_lock().ok_or(TryPrintError::LockBusy)?;
+    writer.write_fmt(args).map_err(|_| TryPrintError::Format)
+}
+
+/// Like `println!`, but colors the line by the keywords it contains
+///
+/// Lines with a word starting with "error" or "panic" are red, with "warn"
+/// yellow, and lines with the word "ok" green (in this order of priority).
+#[macro_export]
+macro_rules! smart_println {
+    () => ($crate::println!());
+    ($($arg:tt)*) => ($crate::vga_buffer::_smart_print(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+/// The longest word prefix `KeywordScanner` has to look at
+const MAX_KEYWORD_LEN: usize = 5;
+
+/// An `fmt::Write` sink that looks for log keywords instead of storing text
+///
+/// STUDY NOTE: Without a heap we can't format the line into a string first.
+/// Instead we format it twice: once into this scanner to pick the color, and
+/// once into the WRITER. The scanner only remembers the beginning of the
+/// current word, so words split across `write_str` calls are still found.
+#[derive(Default)]
+struct KeywordScanner {
+    word: [u8; MAX_KEYWORD_LEN],
+    word_len: usize,
+    color: Option<Color>,
+}
+
+impl KeywordScanner {
+    /// Checks the word that just ended and starts a new one
+    fn end_word(&mut self) {
+        let word = &self.word[..self.word_len.min(MAX_KEYWORD_LEN)];
+        let color = if word.starts_with(b"error") || word.starts_with(b"panic") {
+            Some(Color::Red)
+        } else if word.starts_with(b"warn") {
+            Some(Color::Yellow)
+        } else if word == b"ok" && self.word_len == 2 {
+            Some(Color::Green)
+        } else {
+            None
+        };
+        // Keep the most severe color seen so far
+        self.color = match (self.color, color) {
+            (Some(Color::Red), _) | (Some(Color::Yellow), Some(Color::Green)) => self.color,
+            (current, None) => current,
+            (_, found) => found,
+        };
+        self.word_len = 0;
+    }
+}
+
+impl fmt::Write for KeywordScanner {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte.is_ascii_alphabetic() {
+                if self.word_len < MAX_KEYWORD_LEN {
+                    self.word[self.word_len] = byte.to_ascii_lowercase();
+                }
+                self.word_len += 1;
+            } else {
+                self.end_word();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the given formatted string in the color of its keywords
+#[doc(hidden)]
+#[allow(dead_code)]
+pub fn _smart_print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    if !OUTPUT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut scanner = KeywordScanner::default();
+    scanner.write_fmt(args).unwrap();
+    scanner.end_word();
+
+    let mut writer = WRITER.lock();
+    match scanner.color {
+        Some(foreground) => {
+            let background = writer.color_code.background();
+            writer.with_color(foreground, background, |writer| writer.write_fmt(args).unwrap());
+        }
+        None => writer.write_fmt(args).unwrap(),
+    }
+}
+
+// =============================================================================
+// ANIMATED OUTPUT
+// =============================================================================
+
+/// Prints a string one character at a time, pausing after each character
+///
+/// STUDY NOTE: The WRITER lock is taken separately for every character, so
+/// other output can be printed between two characters instead of waiting
+/// for the whole animation to finish.
+///
+/// We don't have a timer interrupt yet, so the pause of `delay_ms`
+/// milliseconds is a busy-wait calibrated against the PIT (see
+/// `pit::delay_us` for how exact it is). Nothing is printed, and there is
+/// no pause, while output is disabled.
+pub fn type_out(s: &str, delay_ms: u64) {
+    if !OUTPUT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut bytes = [0; 4];
+    for c in s.chars() {
+        WRITER.lock().write_string(c.encode_utf8(&mut bytes));
+        pit::delay_ms(delay_ms);
+    }
+}
+
+/// The gray shades a fade steps through, from brightest to black
+const FADE_SHADES: [Color; 4] = [Color::White, Color::LightGray, Color::DarkGray, Color::Black];
+
+/// Returns the color shown for `color` at the given step of a fade
+///
+/// STUDY NOTE: VGA can't dim a color, so we switch to a gray of similar
+/// brightness instead: bright colors count as White, the others as
+/// LightGray. A color is never made brighter than it is.
+fn fade_shade(color: Color, step: usize) -> Color {
+    let brightness = match color {
+        Color::White => 0,
+        Color::DarkGray => 2,
+        Color::Black => 3,
+        color if color as u8 >= 8 => 0,
+        _ => 1,
+    };
+    FADE_SHADES[brightness.max(step).min(FADE_SHADES.len() - 1)]
+}
+
+// =============================================================================
+// PANIC SCREEN
+// =============================================================================
+
+/// Writes text as horizontally centered lines on consecutive rows
+///
+/// STUDY NOTE: To center a line we need to know its length before writing
+/// it, but the panic message only exists as `fmt::Arguments`. Instead of
+/// formatting into an allocated String (the heap may be what panicked), we
+/// collect one screen line at a time in a small stack buffer and write it
+/// out once it is complete.
+struct CenteredLines<'a> {
+    writer: &'a mut Writer,
+    row: usize,
+    line: [u8; MAX_BUFFER_WIDTH],
+    len: usize,
+}
+
+impl<'a> CenteredLines<'a> {
+    fn new(writer: &'a mut Writer, row: usize) -> Self {
+        CenteredLines {
+            writer,
+            row,
+            line: [0; MAX_BUFFER_WIDTH],
+            len: 0,
+        }
+    }
+
+    /// Writes the collected line centered on the current row
+    fn flush_line(&mut self) {
+        if self.row < self.writer.height {
+            let start = (self.writer.width - self.len) / 2;
+            let color_code = self.writer.color_code;
+            for i in 0..self.len {
+                self.writer.write_cell(self.row, start + i, ScreenChar {
+                    ascii_character: self.line[i],
+                    color_code,
+                });
+            }
+        }
+        self.row += 1;
+        self.len = 0;
+    }
+
+    /// Writes out a partially collected last line
+    fn finish(mut self) {
+        if self.len > 0 {
+            self.flush_line();
+        }
+    }
+}
+
+impl fmt::Write for CenteredLines<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            match byte {
+                b'\n' => self.flush_line(),
+                byte => {
+                    if self.len == self.writer.width {
+                        self.flush_line();
+                    }
+                    self.line[self.len] = match byte {
+                        0x20..=0x7e => byte,
+                        _ => self.writer.unsupported_glyph(),
+                    };
+                    self.len += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The most bytes of the panic message that the panic screen shows
+const PANIC_MESSAGE_CAP: usize = 1024;
+
+/// A fixed-size `fmt::Write` buffer that stops accepting text when full
+///
+/// STUDY NOTE: Once the buffer is full, `write_str` returns an error. That
+/// makes `write!` give up right away, so even a huge `Debug` output of a
+/// large structure takes no longer to format than `PANIC_MESSAGE_CAP` bytes.
+struct CappedBuffer {
+    bytes: [u8; PANIC_MESSAGE_CAP],
+    len: usize,
+    truncated: bool,
+}
+
+impl CappedBuffer {
+    fn new() -> Self {
+        CappedBuffer {
+            bytes: [0; PANIC_MESSAGE_CAP],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Formats `args` into a new buffer, cut off at the cap
+    fn format(args: fmt::Arguments) -> Self {
+        use core::fmt::Write;
+        let mut buffer = CappedBuffer::new();
+        let _ = buffer.write_fmt(args);
+        buffer
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole characters are ever stored, so this can't fail
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+
+    /// The marker shown after a message that was cut off
+    fn more(&self) -> &'static str {
+        if self.truncated { " ..." } else { "" }
+    }
+}
+
+impl fmt::Write for CappedBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut end = s.len().min(PANIC_MESSAGE_CAP - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.bytes[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        if end < s.len() {
+            self.truncated = true;
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+/// Fills the screen with red and shows the panic message centered on it
+///
+/// STUDY NOTE: A red screen is hard to miss, unlike a single line that may
+/// scroll away. This path must not allocate, since the panic might have
+/// been caused by the allocator itself.
+pub fn show_panic_screen(info: &PanicInfo) {
+    // Format the message before taking the lock: if the formatter panics
+    // too, the nested panic must not find the WRITER locked
+    let message = CappedBuffer::format(format_args!("{}", info));
+    draw_panic_screen(&mut lock_for_panic(), "KERNEL PANIC", message.as_str(), message.more());
+}
+
+/// Like `show_panic_screen`, but for a failing test: the screen is titled
+/// `=== TEST FAILED ===` and names the test above the panic message
+///
+/// STUDY NOTE: The panic message already contains the file and line of the
+/// failed assertion. There is no serial port yet, so CI only sees QEMU's
+/// exit code; the screen is for reading the failure interactively.
+#[cfg(test)]
+pub fn show_test_failure_screen(info: &PanicInfo, test: &str) {
+    let message = CappedBuffer::format(format_args!("{}\n\n{}", test, info));
+    draw_panic_screen(&mut lock_for_panic(), "TEST FAILED", message.as_str(), message.more());
+}
+
+/// Takes the WRITER lock for the panic screen, even if it is already held
+///
+/// STUDY NOTE: Many panics happen while the WRITER is locked: a bounds
+/// check in a `Writer` method, a layout or banner assertion, and so on.
+/// There is only one CPU and the panicking code never runs again, so the
+/// lock will never be released. Instead of spinning forever, we break it.
+fn lock_for_panic() -> MutexGuard<'static, Writer> {
+    match WRITER.try_lock() {
+        Some(writer) => writer,
+        None => {
+            unsafe { WRITER.force_unlock() };
+            WRITER.lock()
+        }
+    }
+}
+
+/// Draws the panic screen with the given title for an already formatted
+/// message
+///
+/// Tooling that reads the screen or logs relies on the `=== KERNEL PANIC ===`
+/// line, so `test_panic_screen_format` pins the layout.
+fn draw_panic_screen(writer: &mut Writer, title: &str, message: &str, more: &str) {
+    use core::fmt::Write;
+    let (width, height) = (writer.width, writer.height);
+    writer.set_color(Color::White, Color::Red);
+    writer.fill_region(0, 0, width, height, Color::Red);
+
+    let mut lines = CenteredLines::new(writer, height / 3);
+    let _ = write!(
+        lines,
+        "=== {} ===\n\n{}{}\n\n===================",
+        title,
+        message,
+        more
+    );
+    lines.finish();
+}
+
+// =============================================================================
+// TESTING
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a writer over a zeroed 80x25 buffer in ordinary memory
+    ///
+    /// STUDY NOTE: This is a macro rather than a function so that every
+    /// place it is used gets a static buffer of its own. `Buffer::at` is
+    /// then still called only once per address.
+    macro_rules! mock_writer {
+        () => {
+            mock_writer!(DEFAULT_COLOR)
+        };
+        ($color:expr) => {{
+            static mut MOCK_BUFFER: [u16; MAX_BUFFER_CELLS] = [0; MAX_BUFFER_CELLS];
+            Writer::new(unsafe { Buffer::at(&raw mut MOCK_BUFFER as usize) }, $color)
+        }};
+    }
+
+    #[test_case]
+    fn test_init_twice() {
+        init();
+        init();
+        assert!(READY.load(Ordering::Acquire));
+        assert_eq!(WRITER.lock().width(), BUFFER_WIDTH);
+    }
+
+    #[test_case]
+    fn test_panic_screen_format() {
+        let mut writer = WRITER.lock();
+        let original = writer.color_code;
+        draw_panic_screen(&mut writer, "KERNEL PANIC", "test panic message", " ...");
+        let centered = |writer: &Writer, row: usize, text: &str| {
+            let start = (BUFFER_WIDTH - text.len()) / 2;
+            text.bytes()
+                .enumerate()
+                .all(|(i, byte)| writer.read_char_at(row, start + i).ascii_character == byte)
+        };
+        let top = BUFFER_HEIGHT / 3;
+        let banner = centered(&writer, top, "=== KERNEL PANIC ===");
+        let message = centered(&writer, top + 2, "test panic message ...");
+        let footer = centered(&writer, top + 4, "===================");
+        let color = writer.read_char_at(0, 0).color_code;
+        writer.color_code = original;
+        for row in 0..BUFFER_HEIGHT {
+            writer.clear_row(row);
+        }
+        assert!(banner && message && footer);
+        assert_eq!(color, ColorCode::new(Color::White, Color::Red));
+    }
+
+    #[test_case]
+    fn test_lock_for_panic_breaks_held_lock() {
+        // Like code that panics in the middle of a Writer method
+        core::mem::forget(WRITER.lock());
+        assert!(WRITER.try_lock().is_none());
+        drop(lock_for_panic());
+        assert!(WRITER.try_lock().is_some());
+    }
+
+    #[test_case]
+    fn test_println_simple() {
+        println!("test_println_simple output");
+    }
+
+    #[test_case]
+    fn test_println_many() {
+        for _ in 0..200 {
+            println!("test_println_many output");
+        }
+    }
+
+    #[test_case]
+    fn test_println_output() {
+        let s = "Some test string that fits on a single line";
+        println!("{}", s);
+        for (i, c) in s.chars().enumerate() {
+            let screen_char = WRITER.lock().read_char_at(BUFFER_HEIGHT - 2, i);
+            assert_eq!(char::from(screen_char.ascii_character), c);
+        }
+    }
+
+    #[test_case]
+    fn test_new_with_dimensions_80x50() {
+        let mut writer = WRITER.lock();
+        writer.set_dimensions(80, 50);
+        writer.write_string("\nbottom");
+        let screen_char = writer.read_char_at(49, 0);
+        writer.set_dimensions(BUFFER_WIDTH, BUFFER_HEIGHT);
+        assert_eq!(screen_char.ascii_character, b'b');
+    }
+
+    #[test_case]
+    fn test_fill_region_clips_to_screen() {
+        let mut writer = WRITER.lock();
+        writer.fill_region(BUFFER_HEIGHT - 1, BUFFER_WIDTH - 2, 10, 10, Color::Red);
+        let screen_char = writer.read_char_at(BUFFER_HEIGHT - 1, BUFFER_WIDTH - 1);
+        assert_eq!(screen_char.ascii_character, b' ');
+        assert_eq!(screen_char.background(), Color::Red);
+    }
+
+    #[test_case]
+    fn test_draw_box_clips_huge_sizes() {
+        let mut writer = WRITER.lock();
+        let (row, col) = (BUFFER_HEIGHT - 2, BUFFER_WIDTH - 3);
+        writer.draw_box(row, col, usize::MAX, usize::MAX);
+        writer.draw_box(usize::MAX, usize::MAX, usize::MAX, usize::MAX);
+        let glyph = |row, col| writer.read_char_at(row, col).ascii_character;
+        assert_eq!(glyph(row, col), BOX_TOP_LEFT);
+        assert_eq!(glyph(row, BUFFER_WIDTH - 1), BOX_HORIZONTAL);
+        assert_eq!(glyph(BUFFER_HEIGHT - 1, col), BOX_VERTICAL);
+        assert_eq!(glyph(BUFFER_HEIGHT - 1, BUFFER_WIDTH - 1), b' ');
+    }
+
+    /// Collects formatted output in a fixed-size buffer for comparisons
+    struct StrBuf {
+        bytes: [u8; 128],
+        len: usize,
+    }
+
+    impl StrBuf {
+        fn new() -> Self {
+            StrBuf { bytes: [0; 128], len: 0 }
+        }
+
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.bytes[..self.len]).unwrap()
+        }
+    }
+
+    impl fmt::Write for StrBuf {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.bytes.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test_case]
+    fn test_screen_char_debug() {
+        use core::fmt::Write;
+        let screen_char = ScreenChar {
+            ascii_character: b'A',
+            color_code: ColorCode::new(Color::Yellow, Color::Black),
+        };
+        let mut buf = StrBuf::new();
+        write!(buf, "{:?}", screen_char).unwrap();
+        assert_eq!(buf.as_str(), "ScreenChar('A', Yellow on Black)");
+
+        let screen_char = ScreenChar {
+            ascii_character: 0xfe,
+            color_code: ColorCode::new(Color::White, Color::Red),
+        };
+        let mut buf = StrBuf::new();
+        write!(buf, "{:?}", screen_char).unwrap();
+        assert_eq!(buf.as_str(), "ScreenChar(0xFE, White on Red)");
+    }
+
+    #[test_case]
+    fn test_write_kv_aligns_values() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\n");
+        writer.write_kv("Short", format_args!("1"));
+        writer.write_kv("A key that is far too long", format_args!("2"));
+        for row in [BUFFER_HEIGHT - 3, BUFFER_HEIGHT - 2] {
+            let separator = writer.read_char_at(row, KV_KEY_WIDTH + 1);
+            assert_eq!(separator.ascii_character, b':');
+        }
+        let value = writer.read_char_at(BUFFER_HEIGHT - 2, KV_KEY_WIDTH + 3);
+        assert_eq!(value.ascii_character, b'2');
+    }
+
+    #[test_case]
+    fn test_write_columns_flows_items() {
+        let mut writer = WRITER.lock();
+        // Start in the middle of a line: the columns still begin at column 0
+        writer.write_string("\nx");
+        writer.write_columns(&["help", "clear", "a-name-too-long-to-fit-in-a-column", "echo"], 3);
+        // 80 / 3 = 26 cells per column, so the long item is cut to 25
+        let first = BUFFER_HEIGHT - 3;
+        assert_eq!(writer.read_char_at(first - 1, 0).ascii_character, b'x');
+        assert_eq!(writer.read_char_at(first, 0).ascii_character, b'h');
+        assert_eq!(writer.read_char_at(first, 26).ascii_character, b'c');
+        assert_eq!(writer.read_char_at(first, 52).ascii_character, b'a');
+        assert_eq!(writer.read_char_at(first, 76).ascii_character, b'n');
+        assert_eq!(writer.read_char_at(first, 77).ascii_character, b' ');
+        assert_eq!(writer.read_char_at(first + 1, 0).ascii_character, b'e');
+    }
+
+    #[test_case]
+    fn test_write_u64_padded() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\n");
+        writer.write_u64_padded(42, 6, Radix::Decimal);
+        writer.write_u64_zero_padded(0xbeef, 8, Radix::Hex);
+        // Too wide for its field: printed in full
+        writer.write_u64_padded(123_456, 3, Radix::Decimal);
+        let row = BUFFER_HEIGHT - 1;
+        let mut text = [0u8; 20];
+        for (col, byte) in text.iter_mut().enumerate() {
+            *byte = writer.read_char_at(row, col).ascii_character;
+        }
+        assert_eq!(&text, b"    420000BEEF123456");
+    }
+
+    #[test_case]
+    fn test_write_rainbow_changes_color_per_char() {
+        let mut writer = WRITER.lock();
+        let original = writer.color_code;
+        writer.write_rainbow("\nabc");
+        for col in 0..2 {
+            let left = writer.read_char_at(BUFFER_HEIGHT - 1, col);
+            let right = writer.read_char_at(BUFFER_HEIGHT - 1, col + 1);
+            assert_ne!(left.color_code, right.color_code);
+            assert_eq!(left.background(), original.background());
+        }
+        assert_eq!(writer.color_code, original);
+    }
+
+    #[test_case]
+    fn test_scroll_multiple_lines() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\nscroll me");
+        writer.scroll(3);
+        let moved = writer.read_char_at(BUFFER_HEIGHT - 4, 0);
+        assert_eq!(moved.ascii_character, b's');
+        for row in BUFFER_HEIGHT - 3..BUFFER_HEIGHT {
+            assert_eq!(writer.read_char_at(row, 0).ascii_character, b' ');
+        }
+    }
+
+    #[test_case]
+    fn test_shift_rows_up_regions() {
+        let mut writer = WRITER.lock();
+        let last = BUFFER_HEIGHT - 1;
+        for row in 0..BUFFER_HEIGHT {
+            writer.write_string_at(row, 0, if row % 2 == 0 { "even" } else { "odd" });
+        }
+        // A region in the middle leaves the rows around it alone
+        writer.shift_rows_up(5, 10, 1);
+        assert_eq!(writer.read_char_at(4, 0).ascii_character, b'e');
+        assert_eq!(writer.read_char_at(5, 0).ascii_character, b'e');
+        assert_eq!(writer.read_char_at(6, 0).ascii_character, b'o');
+        assert_eq!(writer.read_char_at(10, 0).ascii_character, b' ');
+        assert_eq!(writer.read_char_at(11, 0).ascii_character, b'o');
+        // The whole screen, with a shift larger than the screen
+        writer.shift_rows_up(0, last, 1);
+        assert_eq!(writer.read_char_at(0, 0).ascii_character, b'o');
+        assert_eq!(writer.read_char_at(last, 0).ascii_character, b' ');
+        writer.shift_rows_up(0, last, BUFFER_HEIGHT + 1);
+        for row in 0..BUFFER_HEIGHT {
+            assert_eq!(writer.read_char_at(row, 0).ascii_character, b' ');
+        }
+    }
+
+    #[test_case]
+    fn test_scroll_disabled_keeps_top_rows() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\nstatic\n");
+        let top = writer.read_char_at(BUFFER_HEIGHT - 2, 0);
+
+        writer.set_scroll_enabled(false);
+        for _ in 0..100 {
+            writer.write_string("runaway log line\n");
+        }
+        let top_after = writer.read_char_at(BUFFER_HEIGHT - 2, 0);
+        let last = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+
+        writer.set_scroll_overflow(ScrollOverflow::Drop);
+        writer.write_string("\ndropped");
+        let dropped = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+        writer.set_scroll_overflow(ScrollOverflow::OverwriteLastLine);
+        writer.set_scroll_enabled(true);
+
+        assert_eq!(top_after, top);
+        assert_eq!(last.ascii_character, b' ');
+        assert_eq!(dropped.ascii_character, b' ');
+    }
+
+    #[test_case]
+    fn test_checksum_matches_snapshot_after_scroll() {
+        let mut writer = WRITER.lock();
+        let (snapshot, scrolled) = writer.with_color(Color::White, Color::Blue, |w| {
+            w.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Blue);
+            w.write_string_at(0, 0, "checksum");
+            let snapshot = w.checksum();
+
+            w.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Blue);
+            w.write_string_at(1, 0, "checksum");
+            w.scroll(1);
+            (snapshot, w.checksum())
+        });
+        assert_eq!(snapshot, 0x789e_cd78);
+        assert_eq!(scrolled, snapshot);
+    }
+
+    #[test_case]
+    fn test_attributes_map_per_adapter() {
+        let mut writer = WRITER.lock();
+        let original = writer.color_code;
+        writer.set_color(Color::LightGray, Color::Blue);
+        writer.set_attributes(Attributes { bold: true, underline: true, reverse: false });
+        let color = writer.effective_color();
+        writer.set_adapter(Adapter::Monochrome);
+        let mono = writer.effective_color();
+        writer.set_adapter(Adapter::Color);
+        writer.set_attributes(Attributes::NONE);
+        let plain = writer.effective_color();
+        writer.color_code = original;
+
+        assert_eq!(color, ColorCode::new(Color::White, Color::Blue));
+        assert_eq!(mono, ColorCode(0x09));
+        assert_eq!(plain, ColorCode::new(Color::LightGray, Color::Blue));
+    }
+
+    #[test_case]
+    fn test_color_byte_round_trip() {
+        let mut writer = WRITER.lock();
+        let original = writer.color_byte();
+        writer.set_color_byte(0x9e);
+        writer.write_string("\nB");
+        writer.set_color_byte(original);
+        let cell = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+        assert_eq!(cell.color_byte(), 0x9e);
+        assert_eq!(writer.color_byte(), original);
+    }
+
+    #[test_case]
+    fn test_reverse_video_swaps_nibbles() {
+        let mut writer = WRITER.lock();
+        writer.with_color(Color::Yellow, Color::Blue, |w| {
+            w.write_byte(b'\n');
+            w.set_reverse(true);
+            w.write_byte(b'R');
+            w.set_reverse(false);
+            w.write_byte(b'N');
+        });
+        let reversed = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+        let normal = writer.read_char_at(BUFFER_HEIGHT - 1, 1);
+        // Yellow is bright, so it turns into its dark version (brown) when it
+        // becomes the background; bit 7 would make the text blink
+        assert_eq!(reversed.color_code, ColorCode::new(Color::Blue, Color::Brown));
+        assert_eq!(normal.color_code, ColorCode::new(Color::Yellow, Color::Blue));
+    }
+
+    #[test_case]
+    fn test_replacement_glyph() {
+        let mut writer = WRITER.lock();
+        writer.set_replacement_glyph(b'?');
+        writer.write_string("\n\x07");
+        let replaced = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+        writer.set_replacement_glyph(DEFAULT_REPLACEMENT_GLYPH);
+        assert_eq!(replaced.ascii_character, b'?');
+    }
+
+    #[test_case]
+    fn test_replacement_glyph_is_not_a_control_code() {
+        let mut writer = WRITER.lock();
+        writer.set_replacement_glyph(b'\n');
+        writer.write_string("\na\x07b");
+        let replacement = writer.unsupported_glyph();
+        writer.set_replacement_glyph(DEFAULT_REPLACEMENT_GLYPH);
+        for (col, c) in [b'a', replacement, b'b'].into_iter().enumerate() {
+            assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, col).ascii_character, c);
+        }
+    }
+
+    #[cfg(feature = "ascii-strict")]
+    #[test_case]
+    fn test_ascii_strict_replaces_extended_bytes() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\né");
+        writer.write_bytes(&[0x80, 0xff]);
+        for col in 0..4 {
+            assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, col).ascii_character, b'?');
+        }
+    }
+
+    #[cfg(feature = "ascii-strict")]
+    #[test_case]
+    fn test_ascii_strict_draws_ascii_boxes() {
+        let mut writer = WRITER.lock();
+        writer.draw_box(0, 0, 3, 3);
+        let glyph = |row, col| writer.read_char_at(row, col).ascii_character;
+        assert_eq!([glyph(0, 0), glyph(0, 1), glyph(1, 0)], [b'+', b'-', b'|']);
+        assert_eq!(glyph(2, 2), b'+');
+    }
+
+    #[test_case]
+    fn test_write_string_fast_path_matches_write_byte() {
+        let text = "a line that is long enough to wrap around the right edge of \
+                    the screen at least once,\twith a tab and\nnewlines\n";
+        let mut writer = WRITER.lock();
+        writer.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Black);
+        writer.write_string("\n");
+        writer.write_string(text);
+        let fast = writer.checksum();
+
+        writer.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Black);
+        writer.write_string("\n");
+        let replacement = writer.unsupported_glyph();
+        for byte in text.bytes() {
+            match byte {
+                0x20..=0x7e | b'\n' | b'\t' => writer.write_byte(byte),
+                _ => writer.write_byte(replacement),
+            }
+        }
+        assert_eq!(writer.checksum(), fast);
+    }
+
+    #[test_case]
+    fn test_batched_scroll_matches_line_by_line() {
+        use core::fmt::Write;
+        // More lines than the screen has rows, to batch more than one scroll
+        let mut burst = StrBuf::new();
+        for i in 0..30 {
+            writeln!(burst, "{}", i % 10).unwrap();
+        }
+        let long_line = "a line that is long enough to wrap around the right edge of \
+                         the screen, so it needs two rows\n";
+        let mut writer = WRITER.lock();
+        let mut screens = [0; 2];
+        for (threshold, screen) in [(DEFAULT_SCROLL_BATCH_THRESHOLD, 0), (0, 1)] {
+            writer.set_scroll_batch_threshold(threshold);
+            writer.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Black);
+            writer.write_string("start ");
+            for _ in 0..3 {
+                writer.write_string(burst.as_str());
+                writer.write_string(long_line);
+            }
+            writer.write_string("end");
+            screens[screen] = writer.checksum();
+        }
+        writer.set_scroll_batch_threshold(DEFAULT_SCROLL_BATCH_THRESHOLD);
+        assert_eq!(screens[0], screens[1]);
+    }
+
+    #[test_case]
+    fn test_disabled_output_leaves_buffer_unchanged() {
+        let before = WRITER.lock().checksum();
+        crate::testing::at_test_exit(|| set_output_enabled(true));
+        set_output_enabled(false);
+        println!("this line must not appear");
+        assert_eq!(try_println!("nor this one"), Ok(()));
+        smart_println!("ERROR: nor this one");
+        kv_table!("Nor" => "this one");
+        // A pause this long would never end if type_out waited
+        type_out("nor this one", u64::MAX);
+        assert_eq!(WRITER.lock().checksum(), before);
+    }
+
+    #[test_case]
+    fn test_try_println_fails_while_locked() {
+        assert_eq!(try_println!("free"), Ok(()));
+        let writer = WRITER.lock();
+        assert_eq!(try_println!("busy"), Err(TryPrintError::LockBusy));
+        drop(writer);
+    }
+
+    #[test_case]
+    fn test_smart_println_colors_by_keyword() {
+        // Finish whatever line the cursor is on, so each message starts a row
+        smart_println!();
+        smart_println!("an Error occurred, see the warnings");
+        smart_println!("all ok");
+        smart_println!("a token is not the keyword");
+        let writer = WRITER.lock();
+        // The three messages end up on the rows above the empty last row
+        let first = BUFFER_HEIGHT - 4;
+        for (row, text) in (first..).zip([&b"an"[..], b"al", b"a "]) {
+            for (col, &byte) in text.iter().enumerate() {
+                assert_eq!(writer.read_char_at(row, col).ascii_character, byte);
+            }
+        }
+        let color = |row: usize| writer.read_char_at(row, 0).foreground();
+        assert_eq!(color(first), Color::Red);
+        assert_eq!(color(first + 1), Color::Green);
+        assert_eq!(color(first + 2), writer.color_code.foreground());
+    }
+
+    #[test_case]
+    fn test_capped_buffer_stops_at_cap() {
+        use core::fmt::Write;
+        let mut buffer = CappedBuffer::new();
+        assert!(write!(buffer, "{:?}", [0xffu8; PANIC_MESSAGE_CAP]).is_err());
+        assert!(buffer.truncated);
+        assert_eq!(buffer.len, PANIC_MESSAGE_CAP);
+        // A character that doesn't fit completely is left out
+        let mut buffer = CappedBuffer::new();
+        let _ = write!(buffer, "{:1$}é", "", PANIC_MESSAGE_CAP - 1);
+        assert_eq!(buffer.as_str().len(), PANIC_MESSAGE_CAP - 1);
+    }
+
+    #[test_case]
+    fn test_panic_screen_caps_long_message() {
+        // The same steps as show_panic_screen, with twice the cap of text
+        let message = CappedBuffer::format(format_args!("{:x<1$}", "", 2 * PANIC_MESSAGE_CAP));
+        let mut writer = WRITER.lock();
+        let original = writer.color_code;
+        draw_panic_screen(&mut writer, "KERNEL PANIC", message.as_str(), message.more());
+        let count = |glyph: u8| {
+            (0..BUFFER_HEIGHT)
+                .flat_map(|row| (0..BUFFER_WIDTH).map(move |col| (row, col)))
+                .filter(|&(row, col)| writer.read_char_at(row, col).ascii_character == glyph)
+                .count()
+        };
+        let (shown, dots) = (count(b'x'), count(b'.'));
+        writer.color_code = original;
+        for row in 0..BUFFER_HEIGHT {
+            writer.clear_row(row);
+        }
+        assert_eq!(shown, PANIC_MESSAGE_CAP);
+        assert_eq!(dots, 3);
+    }
+
+    #[test_case]
+    fn test_line_numbers_gutter() {
+        let mut writer = WRITER.lock();
+        // The gutter starts with the next line, so begin on a fresh one
+        writer.write_string("\n");
+        writer.set_line_numbers(true);
+        writer.write_string("a\nb\n");
+        writer.set_line_numbers(false);
+        let first = writer.read_char_at(BUFFER_HEIGHT - 3, LINE_NUMBER_DIGITS - 1);
+        let second = writer.read_char_at(BUFFER_HEIGHT - 2, LINE_NUMBER_DIGITS - 1);
+        let digit = |c: ScreenChar| char::from(c.ascii_character).to_digit(10);
+        let (first, second_digit) = (digit(first), digit(second));
+        assert!(first.is_some() && second_digit.is_some());
+        assert_eq!(first.map(|d| (d + 1) % 10), second_digit);
+        assert_eq!(second.foreground(), Color::DarkGray);
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 2, GUTTER_WIDTH - 2).ascii_character, b'|');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 2, GUTTER_WIDTH).ascii_character, b'b');
+    }
+
+    #[test_case]
+    fn test_line_numbers_gutter_margin_and_tabs() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\n");
+        writer.set_line_numbers(true);
+        // A margin inside the gutter still leaves one column for text
+        writer.set_right_margin(3);
+        writer.write_string("ab\n");
+        writer.set_right_margin(BUFFER_WIDTH);
+        // Tab stops count from the end of the gutter
+        writer.write_string("\tx\n");
+        writer.set_line_numbers(false);
+        let glyph = |row, col| writer.read_char_at(row, col).ascii_character;
+        assert_eq!(glyph(BUFFER_HEIGHT - 4, GUTTER_WIDTH), b'a');
+        assert_eq!(glyph(BUFFER_HEIGHT - 3, GUTTER_WIDTH), b'b');
+        assert_eq!(glyph(BUFFER_HEIGHT - 2, GUTTER_WIDTH + DEFAULT_TAB_WIDTH), b'x');
+    }
+
+    #[test_case]
+    fn test_write_bytes_inline() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\n");
+        writer.write_bytes_inline(&[]);
+        writer.write_bytes_inline(&[0x00, 0xab, 0xff]);
+        for (col, &c) in b"00 AB FF".iter().enumerate() {
+            assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, col).ascii_character, c);
+        }
+    }
+
+    #[test_case]
+    fn test_tab_width() {
+        let mut writer = WRITER.lock();
+        writer.set_tab_width(4);
+        writer.write_string("\na\tb");
+        writer.set_tab_width(DEFAULT_TAB_WIDTH);
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 4).ascii_character, b'b');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 1).ascii_character, b' ');
+    }
+
+    #[test_case]
+    fn test_tab_width_fits_the_mode() {
+        let mut writer = mock_writer!();
+        writer.set_tab_width(60);
+        writer.set_dimensions(40, 25);
+        assert_eq!(writer.tab_width, 40);
+        writer.set_tab_width(60);
+        assert_eq!(writer.tab_width, 40);
+    }
+
+    #[test_case]
+    fn test_right_margin_fits_the_mode() {
+        let mut writer = mock_writer!();
+        // A margin at the edge follows the edge
+        writer.set_dimensions(40, 25);
+        assert_eq!(writer.right_margin, 40);
+        writer.set_dimensions(80, 25);
+        assert_eq!(writer.right_margin, 80);
+        // Other margins are clamped
+        writer.set_right_margin(60);
+        writer.set_dimensions(40, 25);
+        assert_eq!(writer.right_margin, 40);
+        writer.set_right_margin(60);
+        assert_eq!(writer.right_margin, 40);
+    }
+
+    #[test_case]
+    fn test_right_margin() {
+        let mut writer = WRITER.lock();
+        writer.set_right_margin(60);
+        writer.write_string("\n");
+        for _ in 0..61 {
+            writer.write_byte(b'm');
+        }
+        writer.set_right_margin(BUFFER_WIDTH);
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 2, 59).ascii_character, b'm');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 2, 60).ascii_character, b' ');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 0).ascii_character, b'm');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 1).ascii_character, b' ');
+    }
+
+    #[test_case]
+    fn test_write_char_uses_cp437() {
+        let mut writer = WRITER.lock();
+        for c in "\né\t☺".chars() {
+            writer.write_char(c);
+        }
+        let glyph = |col| writer.read_char_at(BUFFER_HEIGHT - 1, col).ascii_character;
+        if cfg!(feature = "ascii-strict") {
+            assert_eq!(glyph(0), b'?');
+        } else {
+            assert_eq!(glyph(0), 0x82);
+            assert_eq!(glyph(DEFAULT_TAB_WIDTH), 0x01);
+        }
+    }
+
+    #[test_case]
+    fn test_write_string_at_truncates_at_row_end() {
+        let mut writer = WRITER.lock();
+        writer.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Black);
+        let long = [b'x'; 100];
+        writer.write_string_at(3, 70, core::str::from_utf8(&long).unwrap());
+        let written = (0..BUFFER_HEIGHT)
+            .flat_map(|row| (0..BUFFER_WIDTH).map(move |col| (row, col)))
+            .filter(|&(row, col)| writer.read_char_at(row, col).ascii_character == b'x')
+            .count();
+        assert_eq!(written, 10);
+        assert_eq!(writer.read_char_at(3, 70).ascii_character, b'x');
+    }
+
+    #[test_case]
+    fn test_write_string_at_wrapped_stays_in_box() {
+        let mut writer = WRITER.lock();
+        writer.fill_region(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT, Color::Black);
+        writer.write_string_at_wrapped(2, 10, 4, 2, "abcdef\nghij");
+        assert_eq!(writer.read_char_at(2, 13).ascii_character, b'd');
+        assert_eq!(writer.read_char_at(2, 14).ascii_character, b' ');
+        assert_eq!(writer.read_char_at(3, 10).ascii_character, b'e');
+        // The text after the newline would be a third line, outside the box
+        assert_eq!(writer.read_char_at(4, 10).ascii_character, b' ');
+    }
+
+    #[test_case]
+    fn test_writer_on_other_buffer() {
+        let before = WRITER.lock().checksum();
+        let mut writer = mock_writer!(ColorCode::new(Color::White, Color::Blue));
+        writer.write_string("mock");
+        let cell = writer.read_char_at(BUFFER_HEIGHT - 1, 0);
+        assert_eq!(cell.ascii_character, b'm');
+        assert_eq!(cell.background(), Color::Blue);
+        assert_eq!(WRITER.lock().checksum(), before);
+    }
+
+    #[test_case]
+    fn test_write_char_drops_combining_marks() {
+        let mut writer = WRITER.lock();
+        for c in "\ne\u{301}\u{fffd}".chars() {
+            writer.write_char(c);
+        }
+        let replacement = writer.unsupported_glyph();
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 0).ascii_character, b'e');
+        assert_eq!(writer.read_char_at(BUFFER_HEIGHT - 1, 1).ascii_character, replacement);
+    }
+
+    #[test_case]
+    fn test_monochrome_ignores_colors() {
+        let mut writer = WRITER.lock();
+        writer.set_monochrome(true);
+        writer.write_string("\n");
+        writer.write_rainbow("rainbow");
+        writer.with_color(Color::Blue, Color::Red, |w| w.write_string(" blue"));
+        writer.set_monochrome(false);
+        for col in 0..12 {
+            let color = writer.read_char_at(BUFFER_HEIGHT - 1, col).color_byte();
+            assert_eq!(color & !0x08, MONOCHROME_COLOR.0);
+        }
+    }
+
+    #[test_case]
+    fn test_fade_out_and_in() {
+        let mut writer = WRITER.lock();
+        writer.write_string("\nfade me");
+        writer.write_rainbow(" and me");
+        let before = writer.checksum();
+        writer.fade_out(0);
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                assert_eq!(writer.read_char_at(row, col).foreground(), Color::Black);
+            }
+        }
+        // Fading in after a fade out brings back the colors from before it
+        writer.fade_in(0);
+        assert_eq!(writer.checksum(), before);
+        // Without a fade out, the current colors are the target
+        writer.fade_in(0);
+        assert_eq!(writer.checksum(), before);
+        assert_eq!(fade_shade(Color::LightRed, 1), Color::LightGray);
+        assert_eq!(fade_shade(Color::Blue, 2), Color::DarkGray);
+        assert_eq!(fade_shade(Color::DarkGray, 1), Color::DarkGray);
+        writer.write_string("\n");
+    }
+
+    #[test_case]
+    fn test_insert_char_at_shifts_right() {
+        let mut writer = WRITER.lock();
+        writer.clear_row(0);
+        writer.write_string_at(0, 0, "abd");
+        writer.write_string_at(0, BUFFER_WIDTH - 1, "z");
+        writer.insert_char_at(0, 2, b'c');
+        for (i, c) in "abcd".bytes().enumerate() {
+            assert_eq!(writer.read_char_at(0, i).ascii_character, c);
         }
+        // The last cell of the row was dropped
+        assert_eq!(writer.read_char_at(0, BUFFER_WIDTH - 1).ascii_character, b' ');
     }
 }
\ No newline at end of file
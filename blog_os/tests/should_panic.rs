@@ -0,0 +1,59 @@
+// Should-Panic Test
+// =================
+// This test kernel checks the part of the panic path that no `#[test_case]`
+// can: with `PanicBehavior::ExitQemu` set, a panic reaches the panic handler
+// and QEMU exits. A `#[test_case]` that panics ends the whole test run, so
+// this runs as a kernel of its own (`harness = false` in Cargo.toml).
+//
+// Study Notes:
+// - The kernel is a binary without a library crate, so the modules under
+//   test are included by path instead of imported
+// - Success and failure are swapped: reaching the panic handler exits QEMU
+//   with the success code, getting past the panic exits with the failure code
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)] // The included modules have #[test_case]s
+#![allow(dead_code, unused_imports)] // Much of the included code is unused here
+
+#[path = "../src/panic.rs"]
+mod panic;
+#[path = "../src/pit.rs"]
+mod pit;
+#[path = "../src/platform.rs"]
+mod platform;
+
+use core::panic::PanicInfo;
+use panic::PanicBehavior;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start() -> ! {
+    panic::set_panic_behavior(PanicBehavior::ExitQemu);
+    should_fail();
+    panic::exit_qemu(panic::QEMU_EXIT_FAILED);
+    halt()
+}
+
+fn should_fail() {
+    assert_eq!(0, 1);
+}
+
+/// Exits QEMU with the success code if the kernel's panic behavior is set
+///
+/// STUDY NOTE: The kernel's own handler ends in `panic::finish`, which would
+/// exit with the failure code here. So this handler checks the switch that
+/// `finish` acts on and exits the way `finish` would, with the success code.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    if panic::panic_behavior() == PanicBehavior::ExitQemu {
+        panic::exit_qemu(panic::QEMU_EXIT_SUCCESS);
+    }
+    panic::exit_qemu(panic::QEMU_EXIT_FAILED);
+    halt()
+}
+
+fn halt() -> ! {
+    loop {
+        core::hint::spin_loop();
+    }
+}